@@ -0,0 +1,126 @@
+use crate::peg_solitaire_environment::{Solitaire, SolitaireAction};
+use crate::problem::Problem;
+use crate::solver::PositionSet;
+
+/// the beam is never made wider than this
+pub const MAX_BEAM_WIDTH: usize = 1 << 22;
+
+/// Counters of a beam search.
+#[derive(Clone, Debug, Default)]
+pub struct BeamStats {
+    /// the width of every try, the last one found the solution or was the last one
+    pub widths: Vec<usize>,
+    /// positions whose jumps were played over all tries
+    pub expanded: u64,
+    /// why the problem was rejected without searching
+    pub infeasible: Option<String>,
+}
+
+/// Play every jump of every position of the beam and keep the `width` positions with the smallest score
+/// among the new ones, counting symmetric positions once. If no solution is found the search is repeated
+/// with a beam four times as wide, until a try never had to drop a position, so it searched everything.
+pub fn beam_search<F>(problem: &Problem, width: usize, score_fn: F) -> (Option<Vec<SolitaireAction>>, BeamStats)
+where
+    F: Fn(&Solitaire) -> f64,
+{
+    let mut stats = BeamStats::default();
+    if let Err(reason) = problem.check_feasible() {
+        stats.infeasible = Some(reason);
+        return (None, stats);
+    }
+    let mut width = width.max(1);
+    loop {
+        stats.widths.push(width);
+        let (solution, complete) = beam_search_with_width(problem, width, &score_fn, &mut stats.expanded);
+        if solution.is_some() || complete || width >= MAX_BEAM_WIDTH {
+            return (solution, stats);
+        }
+        width = (width * 4).min(MAX_BEAM_WIDTH);
+    }
+}
+
+// the solution if one was found, and whether no position had to be dropped
+fn beam_search_with_width<F>(problem: &Problem, width: usize, score_fn: &F, expanded: &mut u64) -> (Option<Vec<SolitaireAction>>, bool)
+where
+    F: Fn(&Solitaire) -> f64,
+{
+    // for every layer the positions of the beam with their index in the previous layer and the jump from there
+    let mut layers: Vec<Vec<(Solitaire, usize, Option<SolitaireAction>)>> = vec![vec![(problem.new_game(), 0, None)]];
+    let symmetries = problem.symmetries();
+    let mut complete = true;
+    loop {
+        let beam = layers.last().unwrap();
+        if let Some(idx) = beam.iter().position(|(env, _, _)| problem.is_solved(env.board)) {
+            let mut solution = Vec::new();
+            let mut idx = idx;
+            for layer in layers.iter().rev() {
+                let (_, parent, action) = &layer[idx];
+                solution.extend(action);
+                idx = *parent;
+            }
+            solution.reverse();
+            return (Some(solution), complete);
+        }
+        let mut seen = PositionSet::default();
+        let mut candidates = Vec::new();
+        for (idx, (env, _, _)) in beam.iter().enumerate() {
+            *expanded += 1;
+            for action in env.actions().unwrap_or_default() {
+                let child = env.with_board(env.simulate_action(&action.value()));
+                let key = child.layout.compress(child.layout.canonical_key_among(child.board, &symmetries).board());
+                if seen.insert(key) {
+                    candidates.push((score_fn(&child), child, idx, action));
+                }
+            }
+        }
+        if candidates.is_empty() {
+            return (None, complete);
+        }
+        if candidates.len() > width {
+            complete = false;
+            candidates.select_nth_unstable_by(width - 1, |a, b| a.0.total_cmp(&b.0));
+            candidates.truncate(width);
+        }
+        layers.push(candidates.into_iter().map(|(_, child, idx, action)| (child, idx, Some(action))).collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::{BoardLayout, Triangle};
+    use crate::heuristic::{Heuristic, ManhattanSpread};
+    use crate::problem::Target;
+    use crate::test_problems::{assert_solves, english_endgame};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_beam_search_central_game() {
+        let problem = Problem::central_game();
+        let (solution, stats) = beam_search(&problem, 100, |env| ManhattanSpread.score(&problem, env.board));
+        assert_solves(&problem, &solution.unwrap());
+        assert_eq!(stats.widths[0], 100);
+        assert!(stats.expanded > 0);
+    }
+
+    #[test]
+    fn test_beam_search_widens_the_beam() {
+        let problem = english_endgame();
+        // without any guidance a single position is not enough
+        let (solution, stats) = beam_search(&problem, 1, |_| 0.);
+        assert_solves(&problem, &solution.unwrap());
+        assert!(stats.widths.len() > 1);
+        assert!(stats.widths.windows(2).all(|w| w[1] == 4 * w[0]));
+    }
+
+    #[test]
+    fn test_beam_search_unsolvable_problem() {
+        // on the triangle with 10 holes a single peg can not be left when starting at a corner
+        let layout = Arc::new(BoardLayout::new(&Triangle::new(4)));
+        let problem = Problem::new(Solitaire::from_layout(layout.clone(), layout.start_position()), Target::AnySinglePeg);
+        let (solution, stats) = beam_search(&problem, 1, |_| 0.);
+        assert_eq!(solution, None);
+        // the last try was wide enough to keep every position
+        assert!(stats.widths.len() > 1);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::pagoda::PagodaPruning;
+use crate::peg_solitaire_environment::{Bitboard, SolitaireAction};
+use crate::problem::Problem;
+use crate::retrograde::Tablebase;
+use crate::solver::PositionSet;
+use std::fmt::Write;
+
+/// The sizes of the layers of both searches and how many positions they had in common.
+#[derive(Clone, Debug, Default)]
+pub struct BidirectionalStats {
+    /// number of positions up to symmetry for every peg count the forward search reached
+    pub forward: Vec<(u32, usize)>,
+    /// the same for the backward search from the target
+    pub backward: Vec<(u32, usize)>,
+    /// positions of the meeting layer which were reached from both sides
+    pub meeting_positions: usize,
+    /// positions the forward search dropped because a pagoda ruled them out
+    pub pagodas: PagodaPruning,
+    /// why the problem was rejected without searching
+    pub infeasible: Option<String>,
+}
+
+impl BidirectionalStats {
+    pub fn report(&self) -> String {
+        let mut s = String::new();
+        for (pegs, size) in self.forward.iter() {
+            writeln!(&mut s, "forward  {:2} pegs: {}", pegs, size).unwrap();
+        }
+        for (pegs, size) in self.backward.iter().rev() {
+            writeln!(&mut s, "backward {:2} pegs: {}", pegs, size).unwrap();
+        }
+        writeln!(&mut s, "{} positions with {} pegs were reached from both sides", self.meeting_positions,
+            self.forward.last().map(|(pegs, _)| *pegs).unwrap_or(0)).unwrap();
+        s
+    }
+}
+
+/// Search forward from the start down to `meet_pegs` pegs and backward from the target up to `meet_pegs`
+/// pegs, and join the two halves of a solution at a position both searches found. Both searches only
+/// store positions up to the symmetries of the problem.
+pub fn meet_in_the_middle(problem: &Problem, meet_pegs: u32) -> (Option<Vec<SolitaireAction>>, BidirectionalStats) {
+    let mut stats = BidirectionalStats { pagodas: PagodaPruning::for_problem(problem), ..Default::default() };
+    if let Err(reason) = problem.check_feasible() {
+        stats.infeasible = Some(reason);
+        return (None, stats);
+    }
+    let layout = problem.layout();
+    let symmetries = problem.symmetries();
+    let key = |board: Bitboard| layout.compress(layout.canonical_key_among(board, &symmetries).board());
+    let start_pegs = problem.start.board.count_pegs();
+    let meet_pegs = meet_pegs.clamp(problem.target.pegs(), start_pegs);
+
+    // forward[n] holds the positions with n pegs which can be reached from the start
+    let mut forward = vec![PositionSet::default(); start_pegs as usize + 1];
+    forward[start_pegs as usize].insert(key(problem.start.board));
+    let mut frontier = vec![problem.start.board];
+    stats.forward.push((start_pegs, 1));
+    for pegs in (meet_pegs..start_pegs).rev() {
+        let mut next = Vec::new();
+        for board in frontier {
+            for action in layout.actions(board) {
+                let child = layout.apply(board, action);
+                if forward[pegs as usize].insert(key(child)) && !stats.pagodas.prunes(child) {
+                    next.push(child);
+                }
+            }
+        }
+        stats.forward.push((pegs, next.len()));
+        frontier = next;
+    }
+
+    let tablebase = Tablebase::build(problem, meet_pegs);
+    let sizes = tablebase.layer_sizes().into_iter().enumerate().skip(problem.target.pegs() as usize);
+    stats.backward = sizes.map(|(pegs, size)| (pegs as u32, size)).collect();
+    let meeting: Vec<Bitboard> = frontier.into_iter().filter(|board| tablebase.can_reach_target(*board) == Some(true)).collect();
+    stats.meeting_positions = meeting.len();
+    let middle = match meeting.first() {
+        Some(board) => *board,
+        None => return (None, stats),
+    };
+
+    // walk back from the meeting position to a position symmetric to the start
+    let mut board = middle;
+    let mut first_half = Vec::new();
+    for pegs in meet_pegs + 1..=start_pegs {
+        let action = layout
+            .unjumps(board)
+            .into_iter()
+            .find(|action| forward[pegs as usize].contains(&key(layout.unapply(board, *action))))
+            .expect("every position of the forward search has a parent in the previous layer");
+        board = layout.unapply(board, action);
+        first_half.push(action);
+    }
+    first_half.reverse();
+    let mut solution = first_half;
+    solution.extend(tablebase.solution(middle).expect("the meeting position is in the table"));
+
+    // the walk may have ended at a symmetric image of the start, which the same symmetry maps back
+    let idx = *symmetries.iter().find(|idx| layout.transform(board, **idx) == problem.start.board).unwrap();
+    let solution = solution.into_iter().map(|action| layout.transform_action(action, idx)).collect();
+    (Some(solution), stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::{BoardLayout, Triangle};
+    use crate::peg_solitaire_environment::Point;
+    use crate::solver::solve;
+    use crate::test_problems::{assert_solves, english_endgame};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_meet_in_the_middle_english_endgame() {
+        let problem = english_endgame();
+        let (solution, stats) = meet_in_the_middle(&problem, 8);
+        assert_solves(&problem, &solution.unwrap());
+        assert_eq!(stats.forward.first(), Some(&(16, 1)));
+        assert_eq!(stats.forward.last().unwrap().0, 8);
+        assert_eq!(stats.backward.first(), Some(&(1, 1)));
+        assert_eq!(stats.backward.last().unwrap().0, 8);
+        assert!(stats.meeting_positions > 0);
+    }
+
+    #[test]
+    fn test_meet_in_the_middle_triangle() {
+        let layout = Arc::new(BoardLayout::new(&Triangle::CRACKER_BARREL));
+        for survivor in [Point { x: 0, y: 0 }, Point { x: 2, y: 3 }] {
+            let problem = Problem::single_vacancy(layout.clone(), Point { x: 0, y: 0 }, survivor);
+            let (solution, _) = meet_in_the_middle(&problem, 7);
+            assert_eq!(solution.is_some(), solve(&problem).is_some());
+            if let Some(solution) = solution {
+                assert_solves(&problem, &solution);
+            }
+        }
+    }
+
+    #[test]
+    fn test_meet_in_the_middle_rejects_infeasible_problem() {
+        let problem = Problem::single_vacancy(BoardLayout::english(), Point { x: 3, y: 3 }, Point { x: 3, y: 2 });
+        let (solution, stats) = meet_in_the_middle(&problem, 16);
+        assert_eq!(solution, None);
+        assert!(stats.infeasible.is_some());
+        assert!(stats.forward.is_empty());
+    }
+}
@@ -0,0 +1,757 @@
+use crate::peg_solitaire_environment::{Bitboard, CanonicalKey, Jump, MultiJump, Point, SolitaireAction, STRIDE};
+use std::sync::{Arc, OnceLock};
+
+/// An affine map `p -> matrix * p + offset` of the grid. The symmetries of a board are given as such maps.
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Debug)]
+pub struct Transform {
+    pub matrix: [[i32; 2]; 2],
+    pub offset: Point,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { matrix: [[1, 0], [0, 1]], offset: Point { x: 0, y: 0 } };
+
+    pub fn new(matrix: [[i32; 2]; 2], offset: Point) -> Self {
+        Transform { matrix, offset }
+    }
+
+    pub fn apply(&self, p: Point) -> Point {
+        Point {
+            x: self.matrix[0][0] * p.x + self.matrix[0][1] * p.y + self.offset.x,
+            y: self.matrix[1][0] * p.x + self.matrix[1][1] * p.y + self.offset.y,
+        }
+    }
+
+    /// the map which first applies `self` and then `next`
+    pub fn then(&self, next: &Transform) -> Transform {
+        let m = |i: usize, j: usize| next.matrix[i][0] * self.matrix[0][j] + next.matrix[i][1] * self.matrix[1][j];
+        Transform {
+            matrix: [[m(0, 0), m(0, 1)], [m(1, 0), m(1, 1)]],
+            offset: next.apply(self.offset),
+        }
+    }
+
+    /// the map of a jump direction, which ignores the offset
+    pub fn apply_to_jump(&self, jump: Jump) -> Jump {
+        self.map_jump(jump).expect("a symmetry has to map jumps onto jumps")
+    }
+
+    fn map_jump(&self, jump: Jump) -> Option<Jump> {
+        let origin = self.apply(Point { x: 0, y: 0 });
+        let moved = self.apply(jump.offset());
+        Jump::from_offset(Point { x: moved.x - origin.x, y: moved.y - origin.y })
+    }
+}
+
+/// The eight symmetries of a square board with `size` cells per side, in the same order as
+/// `peg_solitaire_environment::SYMMETRIES`.
+pub fn square_symmetries(size: i32) -> Vec<Transform> {
+    let n = size - 1;
+    vec![
+        Transform::IDENTITY,
+        Transform::new([[0, 1], [-1, 0]], Point { x: 0, y: n }),
+        Transform::new([[-1, 0], [0, -1]], Point { x: n, y: n }),
+        Transform::new([[0, -1], [1, 0]], Point { x: n, y: 0 }),
+        Transform::new([[1, 0], [0, -1]], Point { x: 0, y: n }),
+        Transform::new([[0, -1], [-1, 0]], Point { x: n, y: n }),
+        Transform::new([[-1, 0], [0, 1]], Point { x: n, y: 0 }),
+        Transform::new([[0, 1], [1, 0]], Point { x: 0, y: 0 }),
+    ]
+}
+
+/// The six directions of the triangular grid. Such boards are drawn on the square grid with every row
+/// shifted by half a cell, so the diagonal from the top left to the bottom right is a line of the grid.
+pub const HEX_JUMPS: [Jump; 6] = [Jump::Right, Jump::Left, Jump::Down, Jump::Up, Jump::DownRight, Jump::UpLeft];
+
+/// The rules which decide in which directions the pegs jump.
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Debug, Default)]
+pub enum RuleSet {
+    /// the jumps of the geometry
+    #[default]
+    Standard,
+    /// additionally the jumps along both diagonals of the square grid
+    Diagonal,
+}
+
+impl RuleSet {
+    pub fn jumps<G: BoardGeometry + ?Sized>(&self, geometry: &G) -> Vec<Jump> {
+        let mut jumps = geometry.jumps();
+        if *self == RuleSet::Diagonal {
+            for jump in [Jump::DownRight, Jump::UpLeft, Jump::UpRight, Jump::DownLeft] {
+                if !jumps.contains(&jump) {
+                    jumps.push(jump);
+                }
+            }
+        }
+        jumps
+    }
+}
+
+/// Describes a board: which cells exist, in which directions pegs jump, the symmetries of the board
+/// and where the hole of the usual start position is.
+pub trait BoardGeometry {
+    fn name(&self) -> String;
+
+    /// all cells of the board, the coordinates have to be non negative
+    fn cells(&self) -> Vec<Point>;
+
+    /// the directions a peg can jump in, in the order actions are generated
+    fn jumps(&self) -> Vec<Jump> {
+        vec![Jump::Right, Jump::Left, Jump::Down, Jump::Up]
+    }
+
+    /// the symmetry group of the board, starting with the identity
+    fn symmetries(&self) -> Vec<Transform>;
+
+    fn default_start_hole(&self) -> Point;
+
+    /// whether the board lies on the triangular grid, which only changes how it is drawn
+    fn triangular_grid(&self) -> bool {
+        false
+    }
+}
+
+// cells of a cross on a square grid: the arms are three cells wide and `arm` cells long
+fn cross_cells(arm: i32) -> Vec<Point> {
+    let size = 2 * arm + 3;
+    let mut cells = Vec::new();
+    for y in 0..size {
+        for x in 0..size {
+            if (arm..arm + 3).contains(&x) || (arm..arm + 3).contains(&y) {
+                cells.push(Point { x, y });
+            }
+        }
+    }
+    cells
+}
+
+/// The English board with 33 holes.
+#[derive(Clone, Copy, Debug)]
+pub struct English;
+
+impl BoardGeometry for English {
+    fn name(&self) -> String {
+        String::from("English")
+    }
+
+    fn cells(&self) -> Vec<Point> {
+        cross_cells(2)
+    }
+
+    fn symmetries(&self) -> Vec<Transform> {
+        square_symmetries(7)
+    }
+
+    fn default_start_hole(&self) -> Point {
+        Point { x: 3, y: 3 }
+    }
+}
+
+/// The French or European board with 37 holes. Since the central game can not be solved on this
+/// board the usual start hole is the one below the top row.
+#[derive(Clone, Copy, Debug)]
+pub struct French;
+
+impl BoardGeometry for French {
+    fn name(&self) -> String {
+        String::from("French")
+    }
+
+    fn cells(&self) -> Vec<Point> {
+        let mut cells = Vec::new();
+        for y in 0..7 {
+            // 3, 5, 7, 7, 7, 5 and 3 cells per row
+            let indent = [2, 1, 0, 0, 0, 1, 2][y as usize];
+            for x in indent..7 - indent {
+                cells.push(Point { x, y });
+            }
+        }
+        cells
+    }
+
+    fn symmetries(&self) -> Vec<Transform> {
+        square_symmetries(7)
+    }
+
+    fn default_start_hole(&self) -> Point {
+        Point { x: 3, y: 1 }
+    }
+}
+
+/// The 45 hole board described by J. C. Wiegleb in 1779: a cross on a 9x9 grid with arms which are
+/// three holes wide and three holes long. It is also known as the German board.
+#[derive(Clone, Copy, Debug)]
+pub struct Wiegleb;
+
+impl BoardGeometry for Wiegleb {
+    fn name(&self) -> String {
+        String::from("Wiegleb")
+    }
+
+    fn cells(&self) -> Vec<Point> {
+        cross_cells(3)
+    }
+
+    fn symmetries(&self) -> Vec<Transform> {
+        square_symmetries(9)
+    }
+
+    fn default_start_hole(&self) -> Point {
+        Point { x: 4, y: 4 }
+    }
+}
+
+// no board has more symmetries than the hexagon
+const MAX_SYMMETRIES: usize = 12;
+
+/// A triangle with `side` holes per side. Row `y` holds the cells `(0, y)` to `(y, y)`.
+#[derive(Clone, Copy, Debug)]
+pub struct Triangle {
+    pub side: i32,
+}
+
+impl Triangle {
+    /// the triangle with 15 holes
+    pub const CRACKER_BARREL: Triangle = Triangle { side: 5 };
+    /// the longest side which fits into a bitboard
+    pub const MAX_SIDE: i32 = 9;
+
+    pub fn new(side: i32) -> Self {
+        Triangle { side }
+    }
+}
+
+impl BoardGeometry for Triangle {
+    fn name(&self) -> String {
+        format!("Triangle {}", self.side)
+    }
+
+    fn cells(&self) -> Vec<Point> {
+        (0..self.side).flat_map(|y| (0..=y).map(move |x| Point { x, y })).collect()
+    }
+
+    fn jumps(&self) -> Vec<Jump> {
+        HEX_JUMPS.to_vec()
+    }
+
+    fn triangular_grid(&self) -> bool {
+        true
+    }
+
+    // every permutation of the distances (x, y - x, side - 1 - y) of a cell to the three sides
+    fn symmetries(&self) -> Vec<Transform> {
+        let n = self.side - 1;
+        vec![
+            Transform::IDENTITY,
+            Transform::new([[-1, 1], [0, 1]], Point { x: 0, y: 0 }),
+            Transform::new([[0, -1], [-1, 0]], Point { x: n, y: n }),
+            Transform::new([[1, 0], [1, -1]], Point { x: 0, y: n }),
+            Transform::new([[-1, 1], [-1, 0]], Point { x: 0, y: n }),
+            Transform::new([[0, -1], [1, -1]], Point { x: n, y: n }),
+        ]
+    }
+
+    fn default_start_hole(&self) -> Point {
+        Point { x: 0, y: 0 }
+    }
+}
+
+/// A hexagon with `side` holes per side. The cells are those of a square with `2 * side - 1` cells per
+/// side which are at most `side - 1` steps away from the diagonal, so at most side `MAX_SIDE` fits a bitboard.
+#[derive(Clone, Copy, Debug)]
+pub struct Hexagon {
+    pub side: i32,
+}
+
+impl Hexagon {
+    /// the longest side which fits into a bitboard
+    pub const MAX_SIDE: i32 = 5;
+
+    pub fn new(side: i32) -> Self {
+        Hexagon { side }
+    }
+
+    fn center(&self) -> Point {
+        Point { x: self.side - 1, y: self.side - 1 }
+    }
+}
+
+impl BoardGeometry for Hexagon {
+    fn name(&self) -> String {
+        format!("Hexagon {}", self.side)
+    }
+
+    fn cells(&self) -> Vec<Point> {
+        let size = 2 * self.side - 1;
+        let mut cells = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                if (x - y).abs() < self.side {
+                    cells.push(Point { x, y });
+                }
+            }
+        }
+        cells
+    }
+
+    fn jumps(&self) -> Vec<Jump> {
+        HEX_JUMPS.to_vec()
+    }
+
+    fn triangular_grid(&self) -> bool {
+        true
+    }
+
+    // the six rotations by 60 degrees around the center, each of them also followed by a reflection
+    fn symmetries(&self) -> Vec<Transform> {
+        let c = self.center().x;
+        let rotation = Transform::new([[1, -1], [1, 0]], Point { x: c, y: 0 });
+        let reflection = Transform::new([[0, 1], [1, 0]], Point { x: 0, y: 0 });
+        let mut rotations = vec![Transform::IDENTITY];
+        for idx in 1..6 {
+            rotations.push(rotations[idx - 1].then(&rotation));
+        }
+        let reflections: Vec<Transform> = rotations.iter().map(|t| t.then(&reflection)).collect();
+        rotations.into_iter().chain(reflections).collect()
+    }
+
+    fn default_start_hole(&self) -> Point {
+        self.center()
+    }
+}
+
+/// Everything needed to play on a geometry, precomputed once: the mask of valid cells, the jumps and
+/// for every symmetry the image of every bit. Boards have at most 64 cells, so positions can also be
+/// stored as a `u64`, see `compress`.
+#[derive(Debug)]
+pub struct BoardLayout {
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    pub mask: u128,
+    /// the cells ordered row by row
+    pub cells: Vec<Point>,
+    pub rules: RuleSet,
+    pub jumps: Vec<Jump>,
+    pub symmetries: Vec<Transform>,
+    pub start_hole: Point,
+    pub triangular_grid: bool,
+    symmetry_tables: Vec<[u128; 128]>,
+    cell_bits: [u64; 128],
+}
+
+impl BoardLayout {
+    pub fn new<G: BoardGeometry + ?Sized>(geometry: &G) -> Self {
+        BoardLayout::with_rules(geometry, RuleSet::Standard)
+    }
+
+    pub fn with_rules<G: BoardGeometry + ?Sized>(geometry: &G, rules: RuleSet) -> Self {
+        let mut cells = geometry.cells();
+        cells.sort_by_key(|p| (p.y, p.x));
+        cells.dedup();
+        let width = cells.iter().map(|p| p.x + 1).max().unwrap_or(0);
+        let height = cells.iter().map(|p| p.y + 1).max().unwrap_or(0);
+        assert!(
+            cells.iter().all(|p| p.x >= 0 && p.y >= 0) && width < STRIDE && height * STRIDE <= 128 && cells.len() <= 64,
+            "the board {} does not fit into a bitboard", geometry.name()
+        );
+        let mask = cells.iter().fold(0, |mask, p| mask | Bitboard::bit(*p));
+
+        let jumps = rules.jumps(geometry);
+        let symmetries = geometry.symmetries();
+        assert!(symmetries.len() <= MAX_SYMMETRIES, "the board {} has too many symmetries", geometry.name());
+        for t in symmetries.iter() {
+            let maps_jumps = jumps.iter().all(|jump| t.map_jump(*jump).is_some_and(|image| jumps.contains(&image)));
+            assert!(maps_jumps, "{:?} does not map the jumps of the board {} onto each other", t, geometry.name());
+        }
+        let symmetry_tables = symmetries
+            .iter()
+            .map(|t| {
+                let mut table = [0; 128];
+                for p in cells.iter() {
+                    let image = t.apply(*p);
+                    assert!(cells.contains(&image), "{:?} is not a symmetry of the board {}", t, geometry.name());
+                    table[Bitboard::index(*p)] = Bitboard::bit(image);
+                }
+                table
+            })
+            .collect();
+
+        let mut cell_bits = [0; 128];
+        for (idx, p) in cells.iter().enumerate() {
+            cell_bits[Bitboard::index(*p)] = 1 << idx;
+        }
+
+        let start_hole = geometry.default_start_hole();
+        assert!(cells.contains(&start_hole));
+        BoardLayout {
+            name: geometry.name(),
+            width,
+            height,
+            mask,
+            cells,
+            rules,
+            jumps,
+            symmetries,
+            start_hole,
+            triangular_grid: geometry.triangular_grid(),
+            symmetry_tables,
+            cell_bits,
+        }
+    }
+
+    /// the layout of the English board, which is only built once
+    pub fn english() -> Arc<BoardLayout> {
+        static ENGLISH: OnceLock<Arc<BoardLayout>> = OnceLock::new();
+        ENGLISH.get_or_init(|| Arc::new(BoardLayout::new(&English))).clone()
+    }
+
+    pub fn is_valid(&self, p: Point) -> bool {
+        p.x >= 0 && p.y >= 0 && p.x < self.width && p.y < self.height && self.mask & Bitboard::bit(p) != 0
+    }
+
+    pub fn is_hole(&self, board: Bitboard, p: Point) -> bool {
+        self.is_valid(p) && !board.is_peg(p)
+    }
+
+    pub fn holes(&self, board: Bitboard) -> Vec<Point> {
+        Bitboard(self.mask & !board.0).pegs()
+    }
+
+    pub fn count_holes(&self, board: Bitboard) -> u32 {
+        self.cells.len() as u32 - board.count_pegs()
+    }
+
+    /// the board with a peg on every cell except `hole`
+    pub fn single_vacancy(&self, hole: Point) -> Bitboard {
+        Bitboard(self.mask & !Bitboard::bit(hole))
+    }
+
+    pub fn start_position(&self) -> Bitboard {
+        self.single_vacancy(self.start_hole)
+    }
+
+    /// bits of all pegs which are able to execute the given jump
+    pub fn movable_pegs(&self, board: Bitboard, jump: Jump) -> u128 {
+        let pegs = board.0;
+        let holes = self.mask & !pegs;
+        let shift = jump.shift();
+        if shift > 0 {
+            pegs & (pegs >> shift) & (holes >> (2 * shift))
+        } else {
+            pegs & (pegs << -shift) & (holes << (-2 * shift))
+        }
+    }
+
+    pub fn actions(&self, board: Bitboard) -> Vec<SolitaireAction> {
+        let mut actions = Vec::new();
+        for jump in self.jumps.iter() {
+            let mut movable = self.movable_pegs(board, *jump);
+            while movable != 0 {
+                actions.push(SolitaireAction { point: Bitboard::point(movable.trailing_zeros()), action: *jump });
+                movable &= movable - 1;
+            }
+        }
+        actions
+    }
+
+    /// Every move of one peg jumping once or several times in a row, together with the position after it.
+    /// Chains of jumps which remove the same pegs and end on the same cell are only listed once.
+    pub fn multi_jumps(&self, board: Bitboard) -> Vec<(MultiJump, Bitboard)> {
+        let mut moves = Vec::new();
+        for peg in board.pegs() {
+            let first = moves.len();
+            let mut stack = vec![(MultiJump { point: peg, jumps: Vec::new() }, board)];
+            while let Some((chain, board)) = stack.pop() {
+                let end = chain.end();
+                for jump in self.jumps.iter() {
+                    let over = end + jump.offset();
+                    if board.is_peg(over) && self.is_hole(board, over + jump.offset()) {
+                        let next = self.apply(board, SolitaireAction { point: end, action: *jump });
+                        if moves[first..].iter().all(|(_, other)| *other != next) {
+                            let mut jumps = chain.jumps.clone();
+                            jumps.push(*jump);
+                            moves.push((MultiJump { point: peg, jumps: jumps.clone() }, next));
+                            stack.push((MultiJump { point: peg, jumps }, next));
+                        }
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    /// the position after the action, which has to be one of `actions(board)`
+    pub fn apply(&self, board: Bitboard, action: SolitaireAction) -> Bitboard {
+        let over = action.point + action.action.offset();
+        let target = over + action.action.offset();
+        debug_assert!(board.is_peg(action.point) && board.is_peg(over) && self.is_hole(board, target));
+        Bitboard(board.0 ^ (Bitboard::bit(action.point) | Bitboard::bit(over) | Bitboard::bit(target)))
+    }
+
+    /// bits of all holes from which a peg could have made the given jump to reach the position: the cell
+    /// jumped over is empty too, and the cell jumped to holds a peg
+    pub fn unjumpable_holes(&self, board: Bitboard, jump: Jump) -> u128 {
+        let pegs = board.0;
+        let holes = self.mask & !pegs;
+        let shift = jump.shift();
+        if shift > 0 {
+            holes & (holes >> shift) & (pegs >> (2 * shift))
+        } else {
+            holes & (holes << -shift) & (pegs << (-2 * shift))
+        }
+    }
+
+    /// the jumps which lead to the position, as the actions played from the previous position
+    pub fn unjumps(&self, board: Bitboard) -> Vec<SolitaireAction> {
+        let mut actions = Vec::new();
+        for jump in self.jumps.iter() {
+            let mut holes = self.unjumpable_holes(board, *jump);
+            while holes != 0 {
+                actions.push(SolitaireAction { point: Bitboard::point(holes.trailing_zeros()), action: *jump });
+                holes &= holes - 1;
+            }
+        }
+        actions
+    }
+
+    /// the position before the action, which has to be one of `unjumps(board)`
+    pub fn unapply(&self, board: Bitboard, action: SolitaireAction) -> Bitboard {
+        let over = action.point + action.action.offset();
+        let target = over + action.action.offset();
+        debug_assert!(self.is_hole(board, action.point) && self.is_hole(board, over) && board.is_peg(target));
+        Bitboard(board.0 ^ (Bitboard::bit(action.point) | Bitboard::bit(over) | Bitboard::bit(target)))
+    }
+
+    pub fn is_finished(&self, board: Bitboard) -> bool {
+        self.jumps.iter().all(|jump| self.movable_pegs(board, *jump) == 0)
+    }
+
+    /// the position after applying the `idx`-th symmetry
+    pub fn transform(&self, board: Bitboard, idx: usize) -> Bitboard {
+        let table = &self.symmetry_tables[idx];
+        let mut bits = board.0;
+        let mut transformed = 0;
+        while bits != 0 {
+            transformed |= table[bits.trailing_zeros() as usize];
+            bits &= bits - 1;
+        }
+        Bitboard(transformed)
+    }
+
+    /// the position under all symmetries of the board
+    pub fn symmetric_boards(&self, board: Bitboard) -> Vec<Bitboard> {
+        let mut transformed = vec![0; self.symmetry_tables.len()];
+        let mut bits = board.0;
+        while bits != 0 {
+            let idx = bits.trailing_zeros() as usize;
+            for (t, table) in transformed.iter_mut().zip(self.symmetry_tables.iter()) {
+                *t |= table[idx];
+            }
+            bits &= bits - 1;
+        }
+        transformed.into_iter().map(Bitboard).collect()
+    }
+
+    /// the smallest encoding among all symmetric variants of the position
+    pub fn canonical_key(&self, board: Bitboard) -> CanonicalKey {
+        let all: [usize; MAX_SYMMETRIES] = std::array::from_fn(|idx| idx);
+        self.canonical_key_among(board, &all[..self.symmetries.len()])
+    }
+
+    /// like `canonical_key`, but only the given symmetries identify positions
+    pub fn canonical_key_among(&self, board: Bitboard, symmetries: &[usize]) -> CanonicalKey {
+        let mut transformed = [0; MAX_SYMMETRIES];
+        let transformed = &mut transformed[..symmetries.len()];
+        let mut bits = board.0;
+        while bits != 0 {
+            let idx = bits.trailing_zeros() as usize;
+            for (t, symmetry) in transformed.iter_mut().zip(symmetries.iter()) {
+                *t |= self.symmetry_tables[*symmetry][idx];
+            }
+            bits &= bits - 1;
+        }
+        CanonicalKey(transformed.iter().copied().min().unwrap_or(board.0))
+    }
+
+    /// the position with one bit per cell, in the order of `cells`
+    pub fn compress(&self, board: Bitboard) -> u64 {
+        let mut bits = board.0;
+        let mut compressed = 0;
+        while bits != 0 {
+            compressed |= self.cell_bits[bits.trailing_zeros() as usize];
+            bits &= bits - 1;
+        }
+        compressed
+    }
+
+    pub fn decompress(&self, compressed: u64) -> Bitboard {
+        let bits = self.cells.iter().enumerate().filter(|(idx, _)| compressed & (1 << idx) != 0).fold(0, |bits, (_, p)| bits | Bitboard::bit(*p));
+        Bitboard(bits)
+    }
+
+    /// the image of an action under the `idx`-th symmetry
+    pub fn transform_action(&self, action: SolitaireAction, idx: usize) -> SolitaireAction {
+        let t = &self.symmetries[idx];
+        SolitaireAction { point: t.apply(action.point), action: t.apply_to_jump(action.action) }
+    }
+
+    /// one digit per valid cell, row by row, like `Display for SolitaireState`
+    pub fn position_string(&self, board: Bitboard) -> String {
+        self.cells.iter().map(|p| if board.is_peg(*p) { '1' } else { '0' }).collect()
+    }
+
+    pub fn board_from_position_string(&self, position: &str) -> Option<Bitboard> {
+        if position.chars().count() != self.cells.len() {
+            return None;
+        }
+        let mut bits = 0;
+        for (p, c) in self.cells.iter().zip(position.chars()) {
+            match c {
+                '1' => bits |= Bitboard::bit(*p),
+                '0' => (),
+                _ => return None,
+            }
+        }
+        Some(Bitboard(bits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peg_solitaire_environment::Solitaire;
+    use rand::Rng;
+
+    #[test]
+    fn test_layouts() {
+        let geometries: [(&dyn BoardGeometry, usize, usize); 3] = [(&English, 33, 4), (&French, 37, 3), (&Wiegleb, 45, 4)];
+        for (geometry, cells, start_actions) in geometries {
+            let layout = BoardLayout::new(geometry);
+            assert_eq!(layout.cells.len(), cells);
+            assert_eq!(layout.mask.count_ones() as usize, cells);
+            assert_eq!(layout.symmetries.len(), 8);
+            assert_eq!(layout.symmetries[0], Transform::IDENTITY);
+
+            let start = layout.start_position();
+            assert_eq!(layout.holes(start), vec![geometry.default_start_hole()]);
+            assert_eq!(layout.actions(start).len(), start_actions);
+            assert_eq!(layout.board_from_position_string(&layout.position_string(start)), Some(start));
+            assert_eq!(layout.compress(start), (1 << cells) - 1 - (1 << layout.cells.iter().position(|p| *p == layout.start_hole).unwrap()));
+            assert_eq!(layout.decompress(layout.compress(start)), start);
+        }
+        assert_eq!(BoardLayout::new(&French).start_hole, Point { x: 3, y: 1 });
+        assert_eq!(BoardLayout::new(&Wiegleb).width, 9);
+    }
+
+    #[test]
+    fn test_hex_layouts() {
+        let geometries: [(&dyn BoardGeometry, usize, usize, usize); 7] = [
+            (&Triangle::CRACKER_BARREL, 15, 6, 2),
+            (&Triangle::new(6), 21, 6, 2),
+            (&Triangle::new(8), 36, 6, 2),
+            (&Hexagon::new(2), 7, 12, 0),
+            (&Hexagon::new(3), 19, 12, 6),
+            (&Hexagon::new(5), 61, 12, 6),
+            (&Triangle::new(4), 10, 6, 2),
+        ];
+        for (geometry, cells, symmetries, start_actions) in geometries {
+            let layout = BoardLayout::new(geometry);
+            assert_eq!(layout.cells.len(), cells);
+            assert_eq!(layout.symmetries.len(), symmetries);
+            assert_eq!(layout.jumps.len(), 6);
+            assert_eq!(layout.actions(layout.start_position()).len(), start_actions);
+
+            // all symmetries are distinct and the group is closed
+            for a in layout.symmetries.iter() {
+                assert_eq!(layout.symmetries.iter().filter(|b| *b == a).count(), 1);
+                for b in layout.symmetries.iter() {
+                    assert!(layout.symmetries.contains(&a.then(b)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_diagonal_rules() {
+        let layout = BoardLayout::with_rules(&English, RuleSet::Diagonal);
+        assert_eq!(layout.jumps.len(), 8);
+        assert_eq!(layout.rules, RuleSet::Diagonal);
+        // a corner of the central square can also be reached along three diagonals
+        let board = layout.single_vacancy(Point { x: 2, y: 2 });
+        assert_eq!(layout.actions(board).len(), 7);
+        assert_eq!(BoardLayout::english().actions(board).len(), 4);
+        assert_eq!(BoardLayout::with_rules(&Wiegleb, RuleSet::Diagonal).jumps.len(), 8);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_diagonal_rules_on_triangular_grid() {
+        // the second diagonal is no line of the triangular grid
+        BoardLayout::with_rules(&Hexagon::new(3), RuleSet::Diagonal);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_hexagon_does_not_fit() {
+        BoardLayout::new(&Hexagon::new(Hexagon::MAX_SIDE + 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_triangle_does_not_fit() {
+        BoardLayout::new(&Triangle::new(Triangle::MAX_SIDE + 1));
+    }
+
+    #[test]
+    fn test_largest_boards_fit() {
+        assert_eq!(BoardLayout::new(&Triangle::new(Triangle::MAX_SIDE)).cells.len(), 45);
+        assert_eq!(BoardLayout::new(&Hexagon::new(Hexagon::MAX_SIDE)).cells.len(), 61);
+    }
+
+    #[test]
+    fn test_symmetries_map_actions() {
+        let mut rng = rand::thread_rng();
+        let triangle = Triangle::new(7);
+        let hexagon = Hexagon::new(4);
+        let layouts = [&English as &dyn BoardGeometry, &French, &Wiegleb, &triangle, &hexagon]
+            .into_iter()
+            .map(BoardLayout::new)
+            .chain([&English as &dyn BoardGeometry, &French].into_iter().map(|g| BoardLayout::with_rules(g, RuleSet::Diagonal)));
+        for layout in layouts {
+            for _ in 0..100 {
+                let board = Bitboard(rng.gen::<u128>() & layout.mask);
+                let key = layout.canonical_key(board);
+                for idx in 0..layout.symmetries.len() {
+                    let transformed = layout.transform(board, idx);
+                    assert_eq!(transformed.count_pegs(), board.count_pegs());
+                    assert_eq!(layout.canonical_key(transformed), key);
+
+                    let mut expected: Vec<SolitaireAction> =
+                        layout.actions(board).into_iter().map(|a| layout.transform_action(a, idx)).collect();
+                    let mut result = layout.actions(transformed);
+                    let key = |a: &SolitaireAction| (a.point, a.action as usize);
+                    expected.sort_by_key(key);
+                    result.sort_by_key(key);
+                    assert_eq!(result, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_display_french_board() {
+        let env = Solitaire::with_geometry(&French);
+        let rows: Vec<String> = env.to_string().lines().map(|row| row.trim_end().to_string()).collect();
+        assert_eq!(rows[0], "       x  x  x");
+        assert_eq!(rows[1], "    x  x     x  x");
+        assert_eq!(rows[3], " x  x  x  x  x  x  x");
+    }
+
+    #[test]
+    fn test_display_triangle() {
+        let env = Solitaire::with_geometry(&Triangle::new(4));
+        let rows: Vec<String> = env.to_string().lines().map(|row| row.trim_end().to_string()).collect();
+        assert_eq!(rows, vec!["", "  x x", " x x x", "x x x x"]);
+    }
+}
@@ -1,13 +1,15 @@
 use crate::peg_solitaire_environment::{Solitaire, SolitaireAction};
+use crate::problem::Problem;
+use crate::reward::RewardModel;
 use crate::state_function::StateFunction;
 use std::time::Instant;
 
-pub fn simulate_and_get_least_played_action(s: &mut StateFunction, env: &Solitaire) -> SolitaireAction {
+pub fn simulate_and_get_least_played_action(s: &mut StateFunction, problem: &Problem, env: &Solitaire) -> SolitaireAction {
     let mut state_counter = i32::MAX;
     let mut prefered_action = env.actions().unwrap()[0]; // just initialize this with first action
     for action in env.actions().unwrap().iter() {
-        let (state, holes, pegs) = env.simulate_action(&action.value());
-        let hash = Solitaire::hash_state_as_string(&state, &holes, &pegs);
+        let board = env.simulate_action(&action.value());
+        let hash = problem.canonical_key(board);
         let c = s.get_state_counter(&hash);
         if c == 0 {
             return *action;
@@ -18,15 +20,18 @@ pub fn simulate_and_get_least_played_action(s: &mut StateFunction, env: &Solitai
         }
 
     }
-    prefered_action.clone()
+    prefered_action
 }
 
+/// play `repetitions` games of the problem, scored by `rewards`
 pub fn brute_force_solving(
+    problem: &Problem,
     repetitions: u128,
+    rewards: Box<dyn RewardModel>,
 ) -> StateFunction
     {
     
-    let mut s = StateFunction::new();
+    let mut s = StateFunction { rewards, ..StateFunction::new() };
     let mut length = 0;
     
     let now = Instant::now();
@@ -38,7 +43,7 @@ pub fn brute_force_solving(
             }
 
             length = s.qs.len();
-            let dummy_state = Solitaire::new().hash_as_str();
+            let dummy_state = problem.canonical_key(problem.start.board);
             println!(
                 "Repetition: {} of {} -- after {} seconds. Length of s {}. This is the best yet {:?}",
                 idx,
@@ -49,34 +54,50 @@ pub fn brute_force_solving(
             );
         }
 
-        let mut env = Solitaire::new();
+        let mut env = problem.new_game();
         let mut reward = 0.;
         let mut state_vec = Vec::new();
         let mut visited_states = Vec::new();
 
-        let mut action = simulate_and_get_least_played_action(&mut s, &env);
-        let hash = env.hash_as_str();
+        let mut action = simulate_and_get_least_played_action(&mut s, problem, &env);
+        let hash = problem.canonical_key(env.board);
         state_vec.push(hash);
-        visited_states.push(env.state.to_string());
+        visited_states.push(env.layout.position_string(env.board));
         while !env.finished() {
-            reward += env.take_action(&action.value());
-            let hash = env.hash_as_str();
+            let board = env.board;
+            env.take_action(&action.value());
+            reward += s.rewards.reward(problem, board, env.board);
+            let hash = problem.canonical_key(env.board);
             state_vec.push(hash);
-            visited_states.push(env.state.to_string());
+            visited_states.push(env.layout.position_string(env.board));
             // println!("These are the actions {:?}", env.actions());
             // println!("Is the game finished {}", env.finished());
             if env.finished() {
-                if env.holes.len() == 32 && env.state.value[3][3] == 1 {
-                    reward += 10.;
-                }
-
                 for (hash, visited_state) in state_vec.iter().zip(visited_states.iter()) {
-                    s.update_state_value_with_fn(hash.clone(), visited_state.clone(), f64::max, reward);
+                    s.update_state_value_with_fn(*hash, visited_state.clone(), f64::max, reward);
                 }
                 break;
             }
-            action = simulate_and_get_least_played_action(&mut s, &env);
+            action = simulate_and_get_least_played_action(&mut s, problem, &env);
         }
     }
     s 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reward::DEFAULT_REWARDS;
+    use crate::test_problems::triangle;
+
+    #[test]
+    fn test_positions_are_stored_on_their_board() {
+        let problem = triangle();
+        let s = brute_force_solving(&problem, 20, Box::new(DEFAULT_REWARDS));
+        assert!(!s.qs.is_empty());
+        for (key, (_, _, position)) in s.qs.iter() {
+            let board = problem.layout().board_from_position_string(position).unwrap();
+            assert_eq!(problem.canonical_key(board), *key);
+        }
+    }
+}
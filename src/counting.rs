@@ -0,0 +1,156 @@
+use crate::pagoda::PagodaPruning;
+use crate::peg_solitaire_environment::{Bitboard, SolitaireAction};
+use crate::problem::Problem;
+use crate::solver::PositionHasher;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+
+type Counts = HashMap<u64, u128, BuildHasherDefault<PositionHasher>>;
+
+/// The number of different sequences of jumps which solve a problem.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SolutionCount {
+    pub solutions: u128,
+    /// solutions which are the image of another one under a symmetry of the problem are counted once
+    pub up_to_symmetry: u128,
+    /// positions whose number of solutions was stored
+    pub positions: usize,
+}
+
+/// Count every solution of the problem. The number of ways to the target is the same for positions which
+/// are symmetric to each other, so it is stored once per canonical position, and the solutions of a
+/// position are the sum over its jumps of the solutions of the position after the jump.
+pub fn count_solutions(problem: &Problem) -> SolutionCount {
+    if problem.check_feasible().is_err() {
+        return SolutionCount::default();
+    }
+    let layout = problem.layout();
+    let start = problem.start.board;
+    let mut counter = SolutionCounter {
+        problem,
+        symmetries: problem.symmetries(),
+        pagodas: PagodaPruning::for_problem(problem),
+        counts: Counts::default(),
+    };
+    let solutions = counter.count(start);
+
+    // By Burnside's lemma the number of classes of symmetric solutions is the average number of solutions
+    // each symmetry of the problem keeps in place. Such a solution only uses jumps the symmetry maps to
+    // themselves.
+    let group: Vec<usize> = counter.symmetries.iter().copied().filter(|idx| layout.transform(start, *idx) == start).collect();
+    let mut fixed = 0;
+    for idx in group.iter() {
+        fixed += match *idx {
+            0 => solutions,
+            _ => counter.count_fixed(start, *idx, &mut Counts::default()),
+        };
+    }
+    assert_eq!(fixed % group.len() as u128, 0);
+    SolutionCount { solutions, up_to_symmetry: fixed / group.len() as u128, positions: counter.counts.len() }
+}
+
+struct SolutionCounter<'a> {
+    problem: &'a Problem,
+    symmetries: Vec<usize>,
+    pagodas: PagodaPruning,
+    counts: Counts,
+}
+
+impl SolutionCounter<'_> {
+    fn count(&mut self, board: Bitboard) -> u128 {
+        if self.problem.is_solved(board) {
+            return 1;
+        }
+        if board.count_pegs() <= self.problem.target.pegs() || self.pagodas.prunes(board) {
+            return 0;
+        }
+        let layout = self.problem.layout();
+        let key = layout.compress(layout.canonical_key_among(board, &self.symmetries).board());
+        if let Some(count) = self.counts.get(&key) {
+            return *count;
+        }
+        let mut count = 0;
+        for action in layout.actions(board) {
+            count += self.count(layout.apply(board, action));
+        }
+        self.counts.insert(key, count);
+        count
+    }
+
+    // the solutions from a position which the `idx`-th symmetry keeps which are kept by the symmetry as well
+    fn count_fixed(&mut self, board: Bitboard, idx: usize, counts: &mut Counts) -> u128 {
+        if self.problem.is_solved(board) {
+            return 1;
+        }
+        if board.count_pegs() <= self.problem.target.pegs() {
+            return 0;
+        }
+        let layout = self.problem.layout();
+        let key = layout.compress(board);
+        if let Some(count) = counts.get(&key) {
+            return *count;
+        }
+        let fixed: Vec<SolitaireAction> = layout.actions(board).into_iter().filter(|a| layout.transform_action(*a, idx) == *a).collect();
+        let mut count = 0;
+        for action in fixed {
+            count += self.count_fixed(layout.apply(board, action), idx, counts);
+        }
+        counts.insert(key, count);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::BoardLayout;
+    use crate::peg_solitaire_environment::Point;
+    use crate::test_problems::{english_endgame, triangle};
+    use std::collections::HashSet;
+
+    // every solution, one jump after the other
+    fn all_solutions(problem: &Problem, board: Bitboard, moves: &mut Vec<SolitaireAction>, solutions: &mut Vec<Vec<SolitaireAction>>) {
+        if problem.is_solved(board) {
+            solutions.push(moves.clone());
+        }
+        for action in problem.layout().actions(board) {
+            moves.push(action);
+            all_solutions(problem, problem.layout().apply(board, action), moves, solutions);
+            moves.pop();
+        }
+    }
+
+    #[test]
+    fn test_count_solutions_triangle() {
+        let problem = triangle();
+        let layout = problem.layout().clone();
+        let mut solutions = Vec::new();
+        all_solutions(&problem, problem.start.board, &mut Vec::new(), &mut solutions);
+        let count = count_solutions(&problem);
+        assert_eq!(count.solutions, solutions.len() as u128);
+        assert!(count.solutions > 0);
+
+        // the reflection which keeps the corner maps solutions onto solutions
+        let reflection = problem.symmetries().into_iter().find(|idx| *idx != 0).unwrap();
+        let classes: HashSet<Vec<(Point, usize)>> = solutions
+            .iter()
+            .map(|solution| {
+                let image: Vec<SolitaireAction> = solution.iter().map(|a| layout.transform_action(*a, reflection)).collect();
+                let key = |s: &[SolitaireAction]| s.iter().map(|a| (a.point, a.action as usize)).collect::<Vec<_>>();
+                key(solution).min(key(&image))
+            })
+            .collect();
+        assert_eq!(count.up_to_symmetry, classes.len() as u128);
+    }
+
+    #[test]
+    fn test_count_solutions_english_endgame() {
+        let problem = english_endgame();
+        let count = count_solutions(&problem);
+        assert!(count.solutions > 0);
+        // the position has no symmetries
+        assert_eq!(count.solutions, count.up_to_symmetry);
+        let infeasible = Problem::single_vacancy(BoardLayout::english(), Point { x: 3, y: 3 }, Point { x: 3, y: 2 });
+        assert_eq!(count_solutions(&infeasible), SolutionCount::default());
+    }
+}
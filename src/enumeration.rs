@@ -0,0 +1,203 @@
+use crate::board_geometry::BoardLayout;
+use crate::peg_solitaire_environment::Bitboard;
+use crate::problem::Problem;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The counts of one layer of the positions reachable from the start of a problem.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LayerCount {
+    pub pegs: u32,
+    /// distinct positions
+    pub positions: u64,
+    /// positions which are not symmetric to each other
+    pub classes: u64,
+    /// positions from which the target can still be reached
+    pub solvable_positions: u64,
+    pub solvable_classes: u64,
+}
+
+/// The result of `enumerate_reachable`: one count for every number of pegs, starting with the start position.
+#[derive(Clone, Debug)]
+pub struct Enumeration {
+    pub layers: Vec<LayerCount>,
+    /// the directory with one file of sorted compressed canonical keys per layer
+    pub dir: PathBuf,
+}
+
+impl Enumeration {
+    pub fn total(&self) -> LayerCount {
+        self.layers.iter().fold(LayerCount::default(), |total, layer| LayerCount {
+            pegs: 0,
+            positions: total.positions + layer.positions,
+            classes: total.classes + layer.classes,
+            solvable_positions: total.solvable_positions + layer.solvable_positions,
+            solvable_classes: total.solvable_classes + layer.solvable_classes,
+        })
+    }
+
+    pub fn report(&self) -> String {
+        let mut s = String::new();
+        writeln!(&mut s, "pegs  positions  up to symmetry  can reach the target  up to symmetry").unwrap();
+        let total = self.total();
+        let rows = self.layers.iter().map(|l| (l.pegs.to_string(), l)).chain([(String::from("all"), &total)]);
+        for (pegs, l) in rows {
+            writeln!(&mut s, "{:>4} {:>10} {:>15} {:>21} {:>15}", pegs, l.positions, l.classes, l.solvable_positions, l.solvable_classes).unwrap();
+        }
+        s
+    }
+
+    /// the compressed canonical keys of the positions with the given number of pegs
+    pub fn read_layer(&self, pegs: u32) -> Result<Vec<u64>, Box<dyn Error>> {
+        read_layer(&layer_path(&self.dir, pegs))
+    }
+}
+
+fn layer_path(dir: &Path, pegs: u32) -> PathBuf {
+    dir.join(format!("layer_{:02}.bin", pegs))
+}
+
+fn write_layer(path: &Path, keys: &[u64]) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for key in keys {
+        writer.write_all(&key.to_le_bytes())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_layer(path: &Path) -> Result<Vec<u64>, Box<dyn Error>> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+    Ok(bytes.chunks_exact(8).map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap())).collect())
+}
+
+// the number of distinct positions symmetric to the board
+fn orbit_size(layout: &BoardLayout, board: Bitboard, symmetries: &[usize]) -> u64 {
+    let mut images: Vec<Bitboard> = symmetries.iter().map(|idx| layout.transform(board, *idx)).collect();
+    images.sort();
+    images.dedup();
+    images.len() as u64
+}
+
+/// Visit every position which can be reached from the start of the problem, one layer of peg counts after the
+/// other, and count the positions of each layer and how many of them can still reach the target. Positions are
+/// identified under the symmetries which keep both the start and the target. Every layer is written to `dir`
+/// as soon as it is complete, so only the layer being built and the one before it are kept in memory.
+pub fn enumerate_reachable(problem: &Problem, dir: &Path) -> Result<Enumeration, Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let layout = problem.layout();
+    let start = problem.start.board;
+    let symmetries: Vec<usize> =
+        problem.symmetries().into_iter().filter(|idx| layout.transform(start, *idx) == start).collect();
+    let key = |board: Bitboard| layout.compress(layout.canonical_key_among(board, &symmetries).board());
+
+    let start_pegs = start.count_pegs();
+    let mut layers = Vec::new();
+    let mut layer = vec![key(start)];
+    let mut pegs = start_pegs;
+    loop {
+        write_layer(&layer_path(dir, pegs), &layer)?;
+        let positions = layer.iter().map(|k| orbit_size(layout, layout.decompress(*k), &symmetries)).sum();
+        layers.push(LayerCount { pegs, positions, classes: layer.len() as u64, ..Default::default() });
+        if pegs == 0 {
+            break;
+        }
+        let mut next = Vec::new();
+        for k in layer.iter() {
+            let board = layout.decompress(*k);
+            for action in layout.actions(board) {
+                next.push(key(layout.apply(board, action)));
+            }
+        }
+        next.sort_unstable();
+        next.dedup();
+        if next.is_empty() {
+            break;
+        }
+        layer = next;
+        pegs -= 1;
+    }
+
+    // going back up, a position can reach the target if the target is reached or one of its children can
+    let mut solvable: Vec<u64> = Vec::new();
+    for count in layers.iter_mut().rev() {
+        let layer = read_layer(&layer_path(dir, count.pegs))?;
+        let mut next_solvable = Vec::new();
+        for k in layer {
+            let board = layout.decompress(k);
+            let reaches = problem.is_solved(board)
+                || layout.actions(board).into_iter().any(|action| solvable.binary_search(&key(layout.apply(board, action))).is_ok());
+            if reaches {
+                count.solvable_positions += orbit_size(layout, board, &symmetries);
+                next_solvable.push(k);
+            }
+        }
+        count.solvable_classes = next_solvable.len() as u64;
+        solvable = next_solvable;
+    }
+    Ok(Enumeration { layers, dir: dir.to_path_buf() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::solve;
+    use crate::test_problems::{english_endgame, triangle};
+    use std::collections::HashSet;
+
+    // every reachable position without any symmetries
+    fn reachable(problem: &Problem) -> HashSet<Bitboard> {
+        let layout = problem.layout();
+        let mut seen = HashSet::from([problem.start.board]);
+        let mut stack = vec![problem.start.board];
+        while let Some(board) = stack.pop() {
+            for action in layout.actions(board) {
+                let child = layout.apply(board, action);
+                if seen.insert(child) {
+                    stack.push(child);
+                }
+            }
+        }
+        seen
+    }
+
+    #[test]
+    fn test_enumerate_triangle() {
+        let problem = triangle();
+        let dir = std::env::temp_dir().join(format!("enumerate_triangle_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let enumeration = enumerate_reachable(&problem, &dir).unwrap();
+
+        let positions = reachable(&problem);
+        assert_eq!(enumeration.total().positions, positions.len() as u64);
+        for count in enumeration.layers.iter() {
+            let layer: Vec<&Bitboard> = positions.iter().filter(|b| b.count_pegs() == count.pegs).collect();
+            assert_eq!(count.positions, layer.len() as u64);
+            let solvable = layer.iter().filter(|b| solve(&Problem::new(problem.start.with_board(***b), problem.target)).is_some());
+            assert_eq!(count.solvable_positions, solvable.count() as u64);
+            assert_eq!(enumeration.read_layer(count.pegs).unwrap().len() as u64, count.classes);
+        }
+        // the corner and the target are on the axis of one reflection
+        assert!(enumeration.total().classes < enumeration.total().positions);
+        assert_eq!(enumeration.layers[0], LayerCount { pegs: 14, positions: 1, classes: 1, solvable_positions: 1, solvable_classes: 1 });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_enumerate_english_endgame() {
+        let problem = english_endgame();
+        let dir = std::env::temp_dir().join(format!("enumerate_english_endgame_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let enumeration = enumerate_reachable(&problem, &dir).unwrap();
+        // the position has no symmetries, so every position is its own class
+        assert_eq!(enumeration.total().positions, reachable(&problem).len() as u64);
+        assert_eq!(enumeration.total().positions, enumeration.total().classes);
+        assert_eq!(enumeration.layers.last().unwrap().solvable_positions, 1);
+        assert!(enumeration.report().lines().last().unwrap().starts_with(" all"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,255 @@
+use crate::board_geometry::BoardLayout;
+use crate::pagoda::PagodaPruning;
+use crate::peg_solitaire_environment::{Bitboard, Point, Solitaire, SolitaireAction, SolitaireState};
+use crate::problem::{Problem, Target};
+use crate::solver::PositionSet;
+use crate::state_function::StateFunction;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// An estimate of how far a position is from the target of a problem, smaller is better.
+pub trait Heuristic {
+    fn name(&self) -> String;
+    fn score(&self, problem: &Problem, board: Bitboard) -> f64;
+    /// why the heuristic can't score the positions of the problem
+    fn check(&self, _problem: &Problem) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// the number of pegs which still have to be removed
+pub struct PegCount;
+
+impl Heuristic for PegCount {
+    fn name(&self) -> String {
+        String::from("peg count")
+    }
+
+    fn score(&self, problem: &Problem, board: Bitboard) -> f64 {
+        board.count_pegs() as f64 - problem.target.pegs() as f64
+    }
+}
+
+/// the sum of the Manhattan distances of the pegs to the target cell, or to the middle of the board
+pub struct ManhattanSpread;
+
+impl Heuristic for ManhattanSpread {
+    fn name(&self) -> String {
+        String::from("Manhattan spread")
+    }
+
+    fn score(&self, problem: &Problem, board: Bitboard) -> f64 {
+        let layout = problem.layout();
+        let center = match problem.target {
+            Target::SinglePegAt(p) => p,
+            _ => Point { x: layout.width / 2, y: layout.height / 2 },
+        };
+        board.pegs().iter().map(|p| ((p.x - center.x).abs() + (p.y - center.y).abs()) as f64).sum()
+    }
+}
+
+/// the number of pegs without a neighbour they could jump over or be jumped over by
+pub struct IsolatedPegs;
+
+impl Heuristic for IsolatedPegs {
+    fn name(&self) -> String {
+        String::from("isolated pegs")
+    }
+
+    fn score(&self, problem: &Problem, board: Bitboard) -> f64 {
+        let jumps = &problem.layout().jumps;
+        let isolated = board.pegs().into_iter().filter(|p| jumps.iter().all(|jump| !board.is_peg(*p + jump.offset()))).count();
+        isolated as f64
+    }
+}
+
+/// Prefers pegs close to the center of the English board, comparing the number of pegs on the groups of
+/// `Solitaire::hash_constant_groups` from the center outwards. Other boards are rejected.
+pub struct ConstantGroups;
+
+impl Heuristic for ConstantGroups {
+    fn name(&self) -> String {
+        String::from("constant groups")
+    }
+
+    fn check(&self, problem: &Problem) -> Result<(), String> {
+        match problem.layout().cells == BoardLayout::english().cells {
+            true => Ok(()),
+            false => Err(format!("the constant groups are only defined on the English board, not on {}", problem.layout().name)),
+        }
+    }
+
+    fn score(&self, _problem: &Problem, board: Bitboard) -> f64 {
+        -Solitaire::hash_constant_groups(&SolitaireState::from(board)) as f64
+    }
+}
+
+/// The value a `StateFunction` learned for the position, positions it hasn't seen score 0.
+pub struct LearnedValue(pub StateFunction);
+
+impl Heuristic for LearnedValue {
+    fn name(&self) -> String {
+        String::from("learned value")
+    }
+
+    fn score(&self, problem: &Problem, board: Bitboard) -> f64 {
+        -self.0.get_state_value(&problem.canonical_key(board)).unwrap_or(0.)
+    }
+}
+
+/// Counters of a best first search.
+#[derive(Clone, Debug, Default)]
+pub struct HeuristicStats {
+    /// positions whose jumps were played
+    pub expanded: u64,
+    /// positions which were scored and put into the queue
+    pub generated: u64,
+    /// positions which were skipped because a symmetric one was seen before
+    pub duplicates: u64,
+    /// the largest number of positions waiting in the queue
+    pub max_queue: usize,
+    pub pagodas: PagodaPruning,
+    /// why the problem was rejected without searching
+    pub infeasible: Option<String>,
+}
+
+/// Best first search which always continues with the position of the best score, going deeper on ties.
+pub struct HeuristicSolver {
+    pub heuristic: Box<dyn Heuristic>,
+    /// give up after expanding this many positions
+    pub max_expanded: Option<u64>,
+}
+
+#[derive(PartialEq)]
+struct Candidate {
+    score: f64,
+    pegs: u32,
+    idx: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    // the best candidate is the largest one for the max heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.score.total_cmp(&self.score).then(other.pegs.cmp(&self.pegs)).then(other.idx.cmp(&self.idx))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl HeuristicSolver {
+    pub fn new(heuristic: Box<dyn Heuristic>) -> Self {
+        HeuristicSolver { heuristic, max_expanded: None }
+    }
+
+    pub fn solve(&self, problem: &Problem) -> (Option<Vec<SolitaireAction>>, HeuristicStats) {
+        let mut stats = HeuristicStats { pagodas: PagodaPruning::for_problem(problem), ..Default::default() };
+        if let Err(reason) = problem.check_feasible().and_then(|_| self.heuristic.check(problem)) {
+            stats.infeasible = Some(reason);
+            return (None, stats);
+        }
+        let layout = problem.layout();
+        let symmetries = problem.symmetries();
+        let key = |board: Bitboard| layout.compress(layout.canonical_key_among(board, &symmetries).board());
+        // every position seen so far with the position and the jump it was reached from
+        let mut nodes: Vec<(Bitboard, Option<(usize, SolitaireAction)>)> = vec![(problem.start.board, None)];
+        let mut seen = PositionSet::default();
+        seen.insert(key(problem.start.board));
+        let mut queue = BinaryHeap::from([Candidate { score: 0., pegs: problem.start.board.count_pegs(), idx: 0 }]);
+
+        while let Some(Candidate { idx, .. }) = queue.pop() {
+            let board = nodes[idx].0;
+            if problem.is_solved(board) {
+                let mut solution = Vec::new();
+                let mut idx = idx;
+                while let Some((parent, action)) = nodes[idx].1 {
+                    solution.push(action);
+                    idx = parent;
+                }
+                solution.reverse();
+                return (Some(solution), stats);
+            }
+            if self.max_expanded.is_some_and(|max| stats.expanded >= max) {
+                break;
+            }
+            stats.expanded += 1;
+            for action in layout.actions(board) {
+                let child = layout.apply(board, action);
+                if !seen.insert(key(child)) {
+                    stats.duplicates += 1;
+                    continue;
+                }
+                if stats.pagodas.prunes(child) {
+                    continue;
+                }
+                stats.generated += 1;
+                nodes.push((child, Some((idx, action))));
+                queue.push(Candidate { score: self.heuristic.score(problem, child), pegs: child.count_pegs(), idx: nodes.len() - 1 });
+            }
+            stats.max_queue = stats.max_queue.max(queue.len());
+        }
+        (None, stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_problems::{assert_solves, english_endgame, triangle};
+
+    #[test]
+    fn test_heuristics_solve_problems() {
+        let triangle = triangle();
+        let heuristics: Vec<Box<dyn Heuristic>> = vec![Box::new(PegCount), Box::new(ManhattanSpread), Box::new(IsolatedPegs), Box::new(ConstantGroups)];
+        for heuristic in heuristics {
+            let solver = HeuristicSolver::new(heuristic);
+            for problem in [english_endgame(), triangle.clone()] {
+                if solver.heuristic.check(&problem).is_err() {
+                    continue;
+                }
+                let (solution, stats) = solver.solve(&problem);
+                assert_solves(&problem, &solution.unwrap());
+                assert!(stats.expanded > 0 && stats.generated >= stats.expanded - 1, "{}", solver.heuristic.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_constant_groups_only_on_the_english_board() {
+        let solver = HeuristicSolver::new(Box::new(ConstantGroups));
+        let (solution, stats) = solver.solve(&triangle());
+        assert_eq!((solution, stats.expanded), (None, 0));
+        assert!(stats.infeasible.unwrap().contains("English"));
+        assert!(ConstantGroups.check(&english_endgame()).is_ok());
+    }
+
+    #[test]
+    fn test_learned_value_guides_the_search() {
+        let problem = english_endgame();
+        let mut state_function = StateFunction::new();
+        state_function.iterate_game(&problem, &problem.start, vec![], vec![], 0., &mut 0);
+        let solver = HeuristicSolver::new(Box::new(LearnedValue(state_function)));
+        let (solution, stats) = solver.solve(&problem);
+        // the learned values lead straight to the target
+        assert_eq!(stats.expanded, 15);
+        assert_solves(&problem, &solution.unwrap());
+    }
+
+    #[test]
+    fn test_give_up_and_infeasible() {
+        let mut solver = HeuristicSolver::new(Box::new(PegCount));
+        solver.max_expanded = Some(3);
+        let (solution, stats) = solver.solve(&Problem::central_game());
+        assert_eq!((solution, stats.expanded), (None, 3));
+
+        let problem = Problem::single_vacancy(BoardLayout::english(), Point { x: 3, y: 3 }, Point { x: 3, y: 2 });
+        let (solution, stats) = solver.solve(&problem);
+        assert_eq!(solution, None);
+        assert!(stats.infeasible.is_some());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::board_geometry::BoardLayout;
+use crate::peg_solitaire_environment::{Bitboard, CanonicalKey, Solitaire, SolitaireState, get_start_state};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Translates the string hashes of `Solitaire::hash_state_as_string`, which are the keys of the
+/// stored tables, into canonical keys of the English board. A legacy hash can only be translated if every position seen
+/// under it is symmetric to every other one, i.e. all of them share the same canonical key.
+#[derive(Debug, Default)]
+pub struct LegacyHashMigration {
+    pub buckets: HashMap<String, HashSet<CanonicalKey>>,
+}
+
+impl LegacyHashMigration {
+    pub fn new() -> Self {
+        LegacyHashMigration { buckets: HashMap::new() }
+    }
+
+    pub fn from_boards<I>(boards: I) -> Self where I: IntoIterator<Item = Bitboard> {
+        let mut migration = LegacyHashMigration::new();
+        for board in boards {
+            migration.insert(board);
+        }
+        migration
+    }
+
+    pub fn insert(&mut self, board: Bitboard) {
+        self.buckets
+            .entry(Solitaire::hash_board_as_string(&board))
+            .or_default()
+            .insert(BoardLayout::english().canonical_key(board));
+    }
+
+    pub fn migrate(&self, legacy_hash: &str) -> Option<CanonicalKey> {
+        match self.buckets.get(legacy_hash) {
+            Some(keys) if keys.len() == 1 => keys.iter().next().copied(),
+            _ => None,
+        }
+    }
+
+    /// migrate a stored row of (hash, position). The position alone gives the key, but the value of
+    /// the row belongs to every position of the hash, so it is only kept if the hash is unambiguous.
+    pub fn migrate_row(&self, legacy_hash: &str, position: &str) -> Option<CanonicalKey> {
+        let layout = BoardLayout::english();
+        let board = layout.board_from_position_string(position)?;
+        if Solitaire::hash_board_as_string(&board) != legacy_hash {
+            return None;
+        }
+        self.migrate(legacy_hash).filter(|key| *key == layout.canonical_key(board))
+    }
+
+    /// all legacy hashes which are shared by positions that are not symmetric to each other
+    pub fn ambiguous_hashes(&self) -> Vec<&String> {
+        let mut hashes: Vec<&String> = self.buckets
+            .iter()
+            .filter(|(_, keys)| keys.len() > 1)
+            .map(|(hash, _)| hash)
+            .collect();
+        hashes.sort();
+        hashes
+    }
+}
+
+/// Result of walking the reachable positions and hashing every one of them the old way.
+#[derive(Debug)]
+pub struct LegacyHashAudit {
+    pub positions: usize,
+    pub moves: usize,
+    pub migration: LegacyHashMigration,
+}
+
+impl LegacyHashAudit {
+    /// every legacy hash which merges positions that are not symmetric, with the keys of these positions
+    pub fn collisions(&self) -> Vec<(&String, Vec<CanonicalKey>)> {
+        self.migration
+            .ambiguous_hashes()
+            .into_iter()
+            .map(|hash| {
+                let mut keys: Vec<CanonicalKey> = self.migration.buckets[hash].iter().copied().collect();
+                keys.sort();
+                (hash, keys)
+            })
+            .collect()
+    }
+
+    /// human readable report, drawing at most `samples` boards for every colliding hash
+    pub fn report(&self, samples: usize) -> String {
+        let collisions = self.collisions();
+        let mut s = String::new();
+        writeln!(
+            &mut s,
+            "Walked {} positions up to {} moves from the start, they map to {} legacy hashes of which {} are shared by positions that are not symmetric",
+            self.positions,
+            self.moves,
+            self.migration.buckets.len(),
+            collisions.len()
+        ).unwrap();
+        for (hash, keys) in collisions {
+            writeln!(&mut s, "\nHash {} is shared by {} positions", hash, keys.len()).unwrap();
+            for key in keys.iter().take(samples) {
+                writeln!(&mut s, "{}", Solitaire::from_board(key.board())).unwrap();
+            }
+        }
+        s
+    }
+}
+
+/// Walk all positions reachable from `get_start_state()` within `max_moves` moves and record, for every
+/// legacy hash, the positions which map to it. All symmetric variants of a position are hashed since the
+/// stored tables contain whatever orientation was visited first.
+pub fn audit_legacy_hashes(max_moves: usize) -> LegacyHashAudit {
+    let layout = BoardLayout::english();
+    let start = Bitboard::from(SolitaireState { value: get_start_state() });
+    let mut migration = LegacyHashMigration::new();
+    let mut layer: HashSet<CanonicalKey> = HashSet::from([layout.canonical_key(start)]);
+    let mut positions = 0;
+    let mut moves = 0;
+    loop {
+        let mut next_layer = HashSet::new();
+        for key in layer.iter() {
+            for board in layout.symmetric_boards(key.board()) {
+                migration.insert(board);
+            }
+            if moves < max_moves {
+                let env = Solitaire::from_board(key.board());
+                for action in env.actions().unwrap_or_default() {
+                    next_layer.insert(layout.canonical_key(env.simulate_action(&action.value())));
+                }
+            }
+        }
+        positions += layer.len();
+        if next_layer.is_empty() {
+            break;
+        }
+        layer = next_layer;
+        moves += 1;
+    }
+    LegacyHashAudit { positions, moves, migration }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peg_solitaire_environment::SolitaireState;
+
+    // two positions after six moves from the start which are not symmetric but share the legacy hash
+    const COLLIDING: [&str; 2] = ["111111111001111100011110111110111", "111111111011111100111100111100111"];
+
+    #[test]
+    fn test_migrate_unambiguous_hash() {
+        let layout = BoardLayout::english();
+        let start = Solitaire::new().board;
+        let migration = LegacyHashMigration::from_boards(layout.symmetric_boards(start));
+        let hash = Solitaire::hash_board_as_string(&start);
+
+        assert_eq!(migration.migrate(&hash), Some(layout.canonical_key(start)));
+        assert_eq!(migration.migrate_row(&hash, &start_position()), Some(layout.canonical_key(start)));
+        assert_eq!(migration.migrate("32_1565.69579_1000000"), None);
+        assert!(migration.ambiguous_hashes().is_empty());
+    }
+
+    #[test]
+    fn test_migrate_ambiguous_hash() {
+        let layout = BoardLayout::english();
+        let boards: Vec<Bitboard> = COLLIDING.iter().map(|p| layout.board_from_position_string(p).unwrap()).collect();
+        let hash = Solitaire::hash_board_as_string(&boards[0]);
+        assert_eq!(hash, "7_36.413685_133784");
+        assert_eq!(hash, Solitaire::hash_board_as_string(&boards[1]));
+        assert_ne!(layout.canonical_key(boards[0]), layout.canonical_key(boards[1]));
+
+        let migration = LegacyHashMigration::from_boards(boards);
+        assert_eq!(migration.migrate(&hash), None);
+        assert_eq!(migration.migrate_row(&hash, COLLIDING[0]), None);
+        assert_eq!(migration.ambiguous_hashes(), vec![&hash]);
+    }
+
+    #[test]
+    fn test_audit_legacy_hashes() {
+        let audit = audit_legacy_hashes(5);
+        assert_eq!(audit.moves, 5);
+        // 1, 1, 2, 8, 39 and 171 positions after 0 to 5 moves
+        assert_eq!(audit.positions, 222);
+        assert!(audit.collisions().is_empty());
+
+        let audit = audit_legacy_hashes(6);
+        assert_eq!(audit.positions, 222 + 719);
+        let collisions = audit.collisions();
+        assert_eq!(collisions.len(), 18);
+        let layout = BoardLayout::english();
+        let expected: Vec<CanonicalKey> = COLLIDING
+            .iter()
+            .map(|p| layout.canonical_key(layout.board_from_position_string(p).unwrap()))
+            .collect();
+        assert!(collisions.iter().any(|(_, keys)| keys.iter().all(|k| expected.contains(k))));
+
+        let report = audit.report(1);
+        assert!(report.contains("18 are shared"));
+        assert!(report.contains("Hash 7_36.413685_133784 is shared by 2 positions"));
+    }
+
+    fn start_position() -> String {
+        SolitaireState::from(Solitaire::new().board).to_string()
+    }
+}
@@ -1,3 +1,27 @@
 pub mod peg_solitaire_environment;
+pub mod beam_search;
+pub mod board_geometry;
+pub mod counting;
+pub mod enumeration;
+pub mod heuristic;
+pub mod bidirectional;
+pub mod linear_value;
+pub mod mcts;
+#[cfg(feature = "nn")]
+pub mod network;
+pub mod min_moves;
+pub mod pagoda;
+pub mod problem;
+pub mod q_learning;
+pub mod retrograde;
+pub mod reward;
+#[cfg(feature = "nn")]
+pub mod selfplay;
+pub mod solvability;
+pub mod solver;
 pub mod state_function;
+pub mod td_learning;
+#[cfg(test)]
+mod test_problems;
 pub mod brute_force_solver;
+pub mod legacy_hash;
@@ -0,0 +1,180 @@
+use crate::board_geometry::BoardLayout;
+use crate::pagoda::{Pagoda, PagodaPruning};
+use crate::peg_solitaire_environment::{Bitboard, Point};
+use crate::problem::Problem;
+use crate::state_function::ValueFunction;
+use std::sync::Arc;
+
+/// Handcrafted features of a position, each of them roughly between 0 and 1.
+#[derive(Clone, Debug)]
+pub struct BoardFeatures {
+    layout: Arc<BoardLayout>,
+    /// the cells which the symmetries of the board map onto each other, on the English board these are the
+    /// groups of `Solitaire::hash_constant_groups`
+    pub groups: Vec<u128>,
+    /// the cells of every block of a three by three split of the board, leaving out empty blocks
+    pub regions: Vec<u128>,
+    /// the pagodas of `PagodaPruning::for_problem` with their value at the target
+    pub pagodas: Vec<(Pagoda, i32)>,
+}
+
+fn bits(cells: &[Point]) -> u128 {
+    cells.iter().fold(0, |bits, p| bits | Bitboard::bit(*p))
+}
+
+impl BoardFeatures {
+    pub fn new(problem: &Problem) -> Self {
+        let layout = problem.layout().clone();
+        let mut groups: Vec<u128> = Vec::new();
+        for p in layout.cells.iter() {
+            let group = bits(&layout.symmetries.iter().map(|t| t.apply(*p)).collect::<Vec<_>>());
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+        let block = |p: &Point| ((3 * p.x + 1) / layout.width, (3 * p.y + 1) / layout.height);
+        let mut regions = Vec::new();
+        for y in 0..3 {
+            for x in 0..3 {
+                let cells: Vec<Point> = layout.cells.iter().filter(|p| block(p) == (x, y)).copied().collect();
+                if !cells.is_empty() {
+                    regions.push(bits(&cells));
+                }
+            }
+        }
+        let pruning = PagodaPruning::for_problem(problem);
+        let pagodas = pruning.pagodas.into_iter().zip(pruning.target_values).collect();
+        BoardFeatures { layout, groups, regions, pagodas }
+    }
+
+    pub fn len(&self) -> usize {
+        self.names().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names = vec![String::from("bias"), String::from("pegs"), String::from("isolated pegs"), String::from("jumps")];
+        names.extend((0..self.groups.len()).map(|idx| format!("group {}", idx)));
+        names.extend((0..self.regions.len()).map(|idx| format!("region {}", idx)));
+        names.extend(self.pagodas.iter().map(|(pagoda, _)| format!("pagoda {}", pagoda.name)));
+        names
+    }
+
+    pub fn features(&self, board: Bitboard) -> Vec<f64> {
+        let cells = self.layout.cells.len() as f64;
+        let share = |group: &u128| (board.0 & group).count_ones() as f64 / group.count_ones() as f64;
+        let pegs = board.pegs();
+        let isolated = pegs.iter().filter(|p| self.layout.jumps.iter().all(|jump| !board.is_peg(**p + jump.offset()))).count();
+        let mut features = vec![
+            1.,
+            pegs.len() as f64 / cells,
+            isolated as f64 / cells,
+            self.layout.actions(board).len() as f64 / cells,
+        ];
+        features.extend(self.groups.iter().map(share));
+        features.extend(self.regions.iter().map(share));
+        let full = bits(&self.layout.cells);
+        // how far the pagoda is above its value at the target, compared to the full board
+        features.extend(self.pagodas.iter().map(|(pagoda, target)| {
+            (pagoda.value(board) - target) as f64 / (pagoda.value(Bitboard(full)) - target).max(1) as f64
+        }));
+        features
+    }
+}
+
+/// The value of a position as a weighted sum of its `BoardFeatures`, which generalizes to positions which
+/// were never played.
+#[derive(Clone, Debug)]
+pub struct LinearValue {
+    pub features: BoardFeatures,
+    pub weights: Vec<f64>,
+}
+
+impl LinearValue {
+    pub fn new(problem: &Problem) -> Self {
+        let features = BoardFeatures::new(problem);
+        let weights = vec![0.; features.len()];
+        LinearValue { features, weights }
+    }
+}
+
+impl ValueFunction for LinearValue {
+    fn name(&self) -> String {
+        String::from("linear")
+    }
+
+    fn value(&self, board: Bitboard) -> f64 {
+        self.features.features(board).iter().zip(self.weights.iter()).map(|(f, w)| f * w).sum()
+    }
+
+    fn gradient(&self, board: Bitboard) -> Vec<(u64, f64)> {
+        self.features.features(board).into_iter().enumerate().filter(|(_, f)| *f != 0.).map(|(idx, f)| (idx as u64, f)).collect()
+    }
+
+    fn add_to_parameter(&mut self, parameter: u64, delta: f64) {
+        self.weights[parameter as usize] += delta;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::q_learning::EpsilonSchedule;
+    use crate::reward::DEFAULT_REWARDS;
+    use crate::td_learning::{evaluate_greedy, Backup, TdConfig, TdLearner, Traces};
+    use crate::test_problems::triangle;
+
+    #[test]
+    fn test_english_features() {
+        let problem = Problem::central_game();
+        let features = BoardFeatures::new(&problem);
+        // the seven groups of cells of `hash_constant_groups`, the center and the four arms, and the pagodas
+        assert_eq!(features.groups.len(), 7);
+        assert_eq!(features.regions.len(), 5);
+        assert_eq!(features.len(), 4 + 7 + 5 + features.pagodas.len());
+        assert_eq!(features.names().len(), features.len());
+
+        let start = features.features(problem.start.board);
+        assert_eq!(start[1], 32. / 33.);
+        assert_eq!(start[2], 0.);
+        assert_eq!(start[3], 4. / 33.);
+        // only the center is empty
+        assert_eq!(start[4..11].iter().filter(|f| **f == 1.).count(), 6);
+        // the groups of `hash_constant_groups`: the center, three rings of four cells, two of eight and the ends
+        let mut sizes: Vec<u32> = features.groups.iter().map(|g| g.count_ones()).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![1, 4, 4, 4, 4, 8, 8]);
+        assert!(start[16..].iter().all(|f| *f > 0. && *f <= 1.));
+    }
+
+    #[test]
+    fn test_gradient_of_linear_value() {
+        let problem = Problem::central_game();
+        let mut value = LinearValue::new(&problem);
+        let board = problem.start.board;
+        for (parameter, derivative) in value.gradient(board) {
+            value.add_to_parameter(parameter, 0.5 * derivative);
+        }
+        let features = value.features.features(board);
+        let expected: f64 = features.iter().map(|f| 0.5 * f * f).sum();
+        assert!((value.value(board) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_linear_value_learns_the_triangle() {
+        let problem = triangle();
+        let before = evaluate_greedy(&problem, &DEFAULT_REWARDS, &LinearValue::new(&problem), 1., 200, 1);
+        for (backup, traces) in [(Backup::TemporalDifference, Traces::Accumulating), (Backup::MonteCarlo, Traces::Replacing)] {
+            let config = TdConfig { alpha: 0.01, lambda: 0.5, traces, backup, episodes: 3000, window: 300, ..Default::default() };
+            let config = TdConfig { epsilon: EpsilonSchedule::Linear { start: 1., end: 0.1, episodes: 2000 }, ..config };
+            let mut learner = TdLearner::with_values(&problem, config, LinearValue::new(&problem));
+            let curve = learner.train();
+            let after = learner.evaluate(200, 1);
+            assert!(after.mean_pegs < before.mean_pegs, "{:?} {:?} {:?}\n{}", backup, before, after, curve.report());
+            assert!(learner.values.weights.iter().all(|w| w.is_finite()));
+        }
+    }
+}
@@ -0,0 +1,551 @@
+use crate::heuristic::Heuristic;
+use crate::peg_solitaire_environment::{Bitboard, SolitaireAction};
+use crate::problem::Problem;
+use crate::reward::{RewardModel, DEFAULT_REWARDS};
+use crate::state_function::ValueFunction;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Prior probabilities of the jumps of a position, used by PUCT to explore promising jumps first.
+pub trait Policy {
+    fn name(&self) -> String;
+    /// one non negative weight for every action, they don't have to sum up to one
+    fn priors(&self, problem: &Problem, board: Bitboard, actions: &[SolitaireAction]) -> Vec<f64>;
+}
+
+/// every jump is as likely as any other
+pub struct UniformPolicy;
+
+impl Policy for UniformPolicy {
+    fn name(&self) -> String {
+        String::from("uniform")
+    }
+
+    fn priors(&self, _problem: &Problem, _board: Bitboard, actions: &[SolitaireAction]) -> Vec<f64> {
+        vec![1. / actions.len() as f64; actions.len()]
+    }
+}
+
+/// the softmax of the negated heuristic score of the position after each jump
+pub struct HeuristicPolicy(pub Box<dyn Heuristic>);
+
+impl Policy for HeuristicPolicy {
+    fn name(&self) -> String {
+        format!("softmax of {}", self.0.name())
+    }
+
+    fn priors(&self, problem: &Problem, board: Bitboard, actions: &[SolitaireAction]) -> Vec<f64> {
+        let layout = problem.layout();
+        let scores: Vec<f64> = actions.iter().map(|a| -self.0.score(problem, layout.apply(board, *a))).collect();
+        let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = scores.iter().map(|s| (s - max).exp()).collect();
+        let sum: f64 = weights.iter().sum();
+        weights.iter().map(|w| w / sum).collect()
+    }
+}
+
+/// How the child to descend into is picked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selection {
+    /// UCB1, every child is tried once before any is tried twice
+    Uct,
+    /// the AlphaZero rule which weighs the exploration of a child with its prior
+    Puct,
+}
+
+/// How a game is finished from a new leaf of the tree.
+pub enum Rollout {
+    Random,
+    /// always the jump to the position with the best score, ties are broken at random
+    Greedy(Box<dyn Heuristic>),
+    /// no game is played, the leaf is worth the reward so far plus its value, as in AlphaZero
+    Value(Box<dyn ValueFunction>),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MctsConfig {
+    /// rollouts before each move
+    pub iterations: u32,
+    pub exploration: f64,
+    pub seed: u64,
+    pub selection: Selection,
+    /// keep the subtree below the chosen jump for the next move
+    pub reuse_tree: bool,
+    /// once a rollout reached the target, follow its jumps instead of the most visited ones
+    pub follow_solution: bool,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            iterations: 1000,
+            exploration: std::f64::consts::SQRT_2,
+            seed: 0,
+            selection: Selection::Uct,
+            reuse_tree: true,
+            follow_solution: false,
+        }
+    }
+}
+
+impl MctsConfig {
+    /// whether the search can choose a jump, which takes at least one rollout
+    pub fn check(&self) -> Result<(), String> {
+        match self.iterations {
+            0 => Err(String::from("at least one rollout per move is needed to choose a jump")),
+            _ => Ok(()),
+        }
+    }
+}
+
+struct Node {
+    board: Bitboard,
+    action: Option<SolitaireAction>,
+    prior: f64,
+    visits: u32,
+    total: f64,
+    // empty until the node is expanded
+    children: Vec<usize>,
+    expanded: bool,
+}
+
+impl Node {
+    fn new(board: Bitboard, action: Option<SolitaireAction>, prior: f64) -> Self {
+        Node { board, action, prior, visits: 0, total: 0., children: Vec::new(), expanded: false }
+    }
+}
+
+/// One move of a game played by `Mcts::play`.
+#[derive(Clone, Debug)]
+pub struct MoveLog {
+    /// pegs on the board before the move
+    pub pegs: u32,
+    pub action: SolitaireAction,
+    /// rollouts through the chosen jump
+    pub visits: u32,
+    /// the average finish reward of these rollouts
+    pub mean: f64,
+    /// nodes of the tree when the move was chosen
+    pub tree_size: usize,
+    /// nodes which were kept from the search of the move before
+    pub reused: usize,
+}
+
+/// A game played by `Mcts::play`.
+#[derive(Clone, Debug, Default)]
+pub struct Playout {
+    pub moves: Vec<MoveLog>,
+    /// the sum of the rewards of the jumps of the game
+    pub reward: f64,
+    pub solved: bool,
+    pub rollouts: u64,
+    /// the best reward of any rollout which was played to the end and the jumps of that game
+    pub best_reward: f64,
+    pub best_game: Vec<SolitaireAction>,
+}
+
+impl Playout {
+    /// one tab separated line per move
+    pub fn log(&self, game: usize) -> String {
+        let mut s = String::new();
+        for (idx, m) in self.moves.iter().enumerate() {
+            writeln!(
+                &mut s,
+                "{}\t{}\t{}\t{}\t{}\t{:?}\t{}\t{:.3}\t{}\t{}",
+                game, idx, m.pegs, m.action.point.x, m.action.point.y, m.action.action, m.visits, m.mean, m.tree_size, m.reused
+            )
+            .unwrap();
+        }
+        s
+    }
+}
+
+/// Write the moves of the games with a header, and a summary line per game at the end.
+pub fn write_playout_log(path: &Path, playouts: &[Playout]) -> io::Result<()> {
+    let mut s = String::from("game\tmove\tpegs\tx\ty\tjump\tvisits\tmean\ttree_size\treused\n");
+    for (game, playout) in playouts.iter().enumerate() {
+        s.push_str(&playout.log(game));
+    }
+    for (game, playout) in playouts.iter().enumerate() {
+        writeln!(
+            &mut s,
+            "# game {}: reward {}, solved {}, {} rollouts, best rollout reward {}",
+            game, playout.reward, playout.solved, playout.rollouts, playout.best_reward
+        )
+        .unwrap();
+    }
+    fs::write(path, s)
+}
+
+/// Monte Carlo tree search which plays a problem one jump after the other. Before each jump it runs
+/// `config.iterations` rollouts from the current position and then takes the jump which was visited most,
+/// or with `config.follow_solution` the next jump of the first rollout which reached the target.
+pub struct Mcts<'a> {
+    pub problem: &'a Problem,
+    pub config: MctsConfig,
+    pub policy: Box<dyn Policy>,
+    pub rollout: Rollout,
+    /// how the jumps of the games are rewarded, `DEFAULT_REWARDS` unless set
+    pub rewards: &'a dyn RewardModel,
+    rng: StdRng,
+    nodes: Vec<Node>,
+    best: (f64, Vec<SolitaireAction>),
+    // the jumps of the first rollout which reached the target
+    solution: Option<Vec<SolitaireAction>>,
+    rollouts: u64,
+}
+
+impl<'a> Mcts<'a> {
+    /// the search of the problem, or why the config can't play it
+    pub fn new(problem: &'a Problem, config: MctsConfig, policy: Box<dyn Policy>, rollout: Rollout) -> Result<Self, String> {
+        config.check()?;
+        Ok(Mcts {
+            problem,
+            config,
+            policy,
+            rollout,
+            rewards: &DEFAULT_REWARDS,
+            rng: StdRng::seed_from_u64(config.seed),
+            nodes: vec![Node::new(problem.start.board, None, 1.)],
+            best: (f64::NEG_INFINITY, Vec::new()),
+            solution: None,
+            rollouts: 0,
+        })
+    }
+
+    /// the position at the root of the tree
+    pub fn board(&self) -> Bitboard {
+        self.nodes[0].board
+    }
+
+    pub fn tree_size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // the reward of a game which solves the problem if the rewards tell it, the mean rewards are divided by it
+    fn scale(&self) -> f64 {
+        self.rewards.solved_return(self.problem, self.problem.start.board).filter(|r| *r > 0.).unwrap_or(1.)
+    }
+
+    // the position after the jumps from the start of the problem and the sum of their rewards
+    fn play_jumps(&self, jumps: &[SolitaireAction]) -> (Bitboard, f64) {
+        let layout = self.problem.layout();
+        jumps.iter().fold((self.problem.start.board, 0.), |(board, reward), action| {
+            let next = layout.apply(board, *action);
+            (next, reward + self.rewards.reward(self.problem, board, next))
+        })
+    }
+
+    fn expand(&mut self, idx: usize) {
+        let board = self.nodes[idx].board;
+        let actions = self.problem.layout().actions(board);
+        let priors = match actions.is_empty() {
+            true => Vec::new(),
+            false => self.policy.priors(self.problem, board, &actions),
+        };
+        for (action, prior) in actions.into_iter().zip(priors) {
+            let child = self.problem.layout().apply(board, action);
+            self.nodes.push(Node::new(child, Some(action), prior));
+            let child_idx = self.nodes.len() - 1;
+            self.nodes[idx].children.push(child_idx);
+        }
+        self.nodes[idx].expanded = true;
+    }
+
+    fn select(&self, idx: usize) -> usize {
+        let parent = &self.nodes[idx];
+        let scale = self.scale();
+        let score = |child: &Node| {
+            let q = match child.visits {
+                0 => 0.,
+                n => child.total / n as f64 / scale,
+            };
+            match self.config.selection {
+                Selection::Uct if child.visits == 0 => f64::INFINITY,
+                Selection::Uct => q + self.config.exploration * ((parent.visits as f64).ln() / child.visits as f64).sqrt(),
+                Selection::Puct => {
+                    q + self.config.exploration * child.prior * (parent.visits as f64).sqrt() / (1 + child.visits) as f64
+                }
+            }
+        };
+        *parent.children.iter().max_by(|a, b| score(&self.nodes[**a]).total_cmp(&score(&self.nodes[**b]))).unwrap()
+    }
+
+    fn play_out(&mut self, mut board: Bitboard, jumps: &mut Vec<SolitaireAction>) {
+        let layout = self.problem.layout().clone();
+        loop {
+            let actions = layout.actions(board);
+            if actions.is_empty() {
+                return;
+            }
+            // a leaf scored by its value is only played out when it is finished already
+            let action = match &self.rollout {
+                Rollout::Random | Rollout::Value(_) => *actions.choose(&mut self.rng).unwrap(),
+                Rollout::Greedy(heuristic) => {
+                    let scores: Vec<f64> = actions.iter().map(|a| heuristic.score(self.problem, layout.apply(board, *a))).collect();
+                    let best = scores.iter().copied().fold(f64::INFINITY, f64::min);
+                    let ties: Vec<usize> = (0..actions.len()).filter(|i| scores[*i] == best).collect();
+                    actions[ties[self.rng.gen_range(0..ties.len())]]
+                }
+            };
+            jumps.push(action);
+            board = layout.apply(board, action);
+        }
+    }
+
+    /// Run one rollout: walk down the tree, expand the leaf, finish the game and back up its reward.
+    pub fn iterate(&mut self, history: &[SolitaireAction]) {
+        let mut path = vec![0];
+        let mut idx = 0;
+        while self.nodes[idx].expanded && !self.nodes[idx].children.is_empty() {
+            idx = self.select(idx);
+            path.push(idx);
+        }
+        if !self.nodes[idx].expanded {
+            self.expand(idx);
+            if !self.nodes[idx].children.is_empty() {
+                idx = self.select(idx);
+                path.push(idx);
+            }
+        }
+        let mut jumps: Vec<SolitaireAction> = history.to_vec();
+        jumps.extend(path.iter().filter_map(|i| self.nodes[*i].action));
+        let leaf = self.nodes[idx].board;
+        let (end, reward) = match &self.rollout {
+            Rollout::Value(values) if !self.problem.layout().is_finished(leaf) => {
+                let (_, reward) = self.play_jumps(&jumps);
+                (leaf, reward + values.value(leaf))
+            }
+            _ => {
+                self.play_out(leaf, &mut jumps);
+                self.play_jumps(&jumps)
+            }
+        };
+        for i in path {
+            self.nodes[i].visits += 1;
+            self.nodes[i].total += reward;
+        }
+        self.rollouts += 1;
+        if self.solution.is_none() && self.problem.is_solved(end) && self.problem.layout().is_finished(end) {
+            self.solution = Some(jumps.clone());
+        }
+        if reward > self.best.0 && self.problem.layout().is_finished(end) {
+            self.best = (reward, jumps);
+        }
+    }
+
+    /// the most visited jump from the root with the number and average reward of its rollouts
+    pub fn best_action(&self) -> Option<(SolitaireAction, u32, f64)> {
+        self.nodes[0]
+            .children
+            .iter()
+            .map(|idx| &self.nodes[*idx])
+            .max_by(|a, b| a.visits.cmp(&b.visits).then(a.total.total_cmp(&b.total)))
+            .map(|child| (child.action.unwrap(), child.visits, child.total / child.visits.max(1) as f64))
+    }
+
+    /// the share of the rollouts from the root which went through each jump
+    pub fn visit_distribution(&self) -> Vec<(SolitaireAction, f64)> {
+        let children = self.nodes[0].children.iter().map(|idx| &self.nodes[*idx]);
+        let total: u32 = children.clone().map(|child| child.visits).sum();
+        children.map(|child| (child.action.unwrap(), child.visits as f64 / total.max(1) as f64)).collect()
+    }
+
+    // the rollouts through the jump from the root and their average reward
+    fn child_stats(&self, action: SolitaireAction) -> (u32, f64) {
+        let child = self.nodes[0].children.iter().map(|idx| &self.nodes[*idx]).find(|child| child.action == Some(action)).unwrap();
+        (child.visits, child.total / child.visits.max(1) as f64)
+    }
+
+    /// Make the child of the jump the new root. With `reuse_tree` its subtree is kept, otherwise the search
+    /// starts over. Returns the number of nodes which were kept.
+    pub fn advance(&mut self, action: SolitaireAction) -> usize {
+        let child = match self.nodes[0].children.iter().find(|idx| self.nodes[**idx].action == Some(action)) {
+            Some(child) => *child,
+            None => {
+                let board = self.problem.layout().apply(self.board(), action);
+                self.nodes = vec![Node::new(board, None, 1.)];
+                return 0;
+            }
+        };
+        if !self.config.reuse_tree {
+            self.nodes = vec![Node::new(self.nodes[child].board, None, 1.)];
+            return 0;
+        }
+        // copy the subtree breadth first, so the new root ends up at index 0 and children after their parents
+        let mut old = std::mem::take(&mut self.nodes);
+        let mut queue = vec![child];
+        let mut head = 0;
+        while head < queue.len() {
+            let children = old[queue[head]].children.clone();
+            queue.extend(children);
+            head += 1;
+        }
+        let mut new_idx = vec![usize::MAX; old.len()];
+        for (new, idx) in queue.iter().enumerate() {
+            new_idx[*idx] = new;
+        }
+        for idx in queue.iter() {
+            let mut node = std::mem::replace(&mut old[*idx], Node::new(Bitboard(0), None, 0.));
+            node.children = node.children.iter().map(|c| new_idx[*c]).collect();
+            self.nodes.push(node);
+        }
+        self.nodes[0].action = None;
+        self.nodes.len()
+    }
+
+    /// Play a whole game from the start of the problem, starting with a fresh tree.
+    pub fn play(&mut self) -> Playout {
+        self.nodes = vec![Node::new(self.problem.start.board, None, 1.)];
+        self.best = (f64::NEG_INFINITY, Vec::new());
+        self.solution = None;
+        self.rollouts = 0;
+        let mut playout = Playout::default();
+        let mut history = Vec::new();
+        let mut reused = 0;
+        while !self.problem.layout().is_finished(self.board()) {
+            for _ in 0..self.config.iterations {
+                self.iterate(&history);
+            }
+            // the jumps of a rollout which reached the target always start with the game so far
+            let action = match &self.solution {
+                Some(jumps) if self.config.follow_solution => jumps[history.len()],
+                _ => self.best_action().unwrap().0,
+            };
+            let (visits, mean) = self.child_stats(action);
+            playout.moves.push(MoveLog { pegs: self.board().count_pegs(), action, visits, mean, tree_size: self.tree_size(), reused });
+            history.push(action);
+            reused = self.advance(action);
+        }
+        playout.reward = self.play_jumps(&history).1;
+        playout.solved = self.problem.is_solved(self.board());
+        playout.rollouts = self.rollouts;
+        (playout.best_reward, playout.best_game) = self.best.clone();
+        playout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heuristic::ManhattanSpread;
+    use crate::reward::PegsRemoved;
+    use crate::state_function::SOLVED_BONUS;
+    use crate::td_learning::StateValues;
+    use crate::test_problems::{english_endgame, triangle};
+
+    fn assert_legal(problem: &Problem, playout: &Playout) {
+        let mut env = problem.new_game();
+        for m in playout.moves.iter() {
+            assert_eq!(env.board.count_pegs(), m.pegs);
+            assert!(env.actions().unwrap().contains(&m.action));
+            env.take_action(&m.action.value());
+        }
+        assert!(env.finished());
+        assert_eq!(playout.solved, problem.is_solved(env.board));
+    }
+
+    #[test]
+    fn test_mcts_solves_the_triangle() {
+        // the most visited jumps solve it without following a solved rollout
+        let problem = triangle();
+        for selection in [Selection::Uct, Selection::Puct] {
+            let config = MctsConfig { iterations: 1000, selection, ..Default::default() };
+            let mut mcts = Mcts::new(&problem, config, Box::new(UniformPolicy), Rollout::Random).unwrap();
+            let playout = mcts.play();
+            assert_legal(&problem, &playout);
+            assert!(playout.solved, "{:?}", selection);
+            assert_eq!(playout.reward, 13. + SOLVED_BONUS);
+            assert!(playout.best_reward >= playout.reward);
+        }
+    }
+
+    #[test]
+    fn test_follow_solution() {
+        // without a bonus for the target the most visited jumps don't solve it, but the solved rollout does
+        let problem = triangle();
+        for follow_solution in [false, true] {
+            let config = MctsConfig { iterations: 1000, follow_solution, ..Default::default() };
+            let mut mcts = Mcts::new(&problem, config, Box::new(UniformPolicy), Rollout::Random).unwrap();
+            mcts.rewards = &PegsRemoved;
+            let playout = mcts.play();
+            assert_legal(&problem, &playout);
+            assert_eq!(playout.solved, follow_solution);
+            assert_eq!(playout.best_reward, 13.);
+        }
+    }
+
+    #[test]
+    fn test_zero_rollouts() {
+        let problem = english_endgame();
+        let config = MctsConfig { iterations: 0, ..Default::default() };
+        assert!(config.check().is_err());
+        assert!(Mcts::new(&problem, config, Box::new(UniformPolicy), Rollout::Random).is_err());
+    }
+
+    #[test]
+    fn test_mcts_english_endgame() {
+        let problem = english_endgame();
+        let config = MctsConfig { iterations: 200, seed: 7, selection: Selection::Puct, ..Default::default() };
+        let mut mcts = Mcts::new(&problem, config, Box::new(HeuristicPolicy(Box::new(ManhattanSpread))), Rollout::Greedy(Box::new(ManhattanSpread))).unwrap();
+        let playout = mcts.play();
+        assert_legal(&problem, &playout);
+        assert_eq!(playout.rollouts, 200 * playout.moves.len() as u64);
+        // the best rollout is a game of its own
+        let mut env = problem.new_game();
+        for action in playout.best_game.iter() {
+            env.take_action(&action.value());
+        }
+        assert_eq!(playout.best_reward, 16. - env.board.count_pegs() as f64 + if problem.is_solved(env.board) { SOLVED_BONUS } else { 0. });
+    }
+
+    #[test]
+    fn test_value_rollouts() {
+        let problem = triangle();
+        let config = MctsConfig { iterations: 300, selection: Selection::Puct, ..Default::default() };
+        let mut mcts = Mcts::new(&problem, config, Box::new(UniformPolicy), Rollout::Value(Box::new(StateValues::new(&problem)))).unwrap();
+        for _ in 0..config.iterations {
+            mcts.iterate(&[]);
+        }
+        let distribution = mcts.visit_distribution();
+        assert_eq!(distribution.len(), 2);
+        assert!((distribution.iter().map(|(_, share)| share).sum::<f64>() - 1.).abs() < 1e-12);
+        assert_eq!(mcts.best_action().unwrap().0, distribution.iter().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap().0);
+        // only rollouts which reached a finished position count as the best game
+        let end = mcts.best.1.iter().fold(problem.start.board, |board, action| problem.layout().apply(board, *action));
+        assert!(mcts.best.0 == f64::NEG_INFINITY || problem.layout().is_finished(end));
+        let playout = mcts.play();
+        assert_legal(&problem, &playout);
+    }
+
+    #[test]
+    fn test_tree_reuse_and_seeds() {
+        let problem = english_endgame();
+        let config = MctsConfig { iterations: 100, seed: 3, ..Default::default() };
+        let mut mcts = Mcts::new(&problem, config, Box::new(UniformPolicy), Rollout::Random).unwrap();
+        let playout = mcts.play();
+        // after the first move part of the tree is kept
+        assert_eq!(playout.moves[0].reused, 0);
+        assert!(playout.moves[1].reused > 0 && playout.moves[1].reused <= playout.moves[0].tree_size);
+        // the same seed plays the same game
+        let mut again = Mcts::new(&problem, config, Box::new(UniformPolicy), Rollout::Random).unwrap();
+        let actions = |p: &Playout| p.moves.iter().map(|m| m.action).collect::<Vec<_>>();
+        assert_eq!(actions(&again.play()), actions(&playout));
+
+        let config = MctsConfig { reuse_tree: false, ..config };
+        let mut fresh = Mcts::new(&problem, config, Box::new(UniformPolicy), Rollout::Random).unwrap();
+        assert!(fresh.play().moves.iter().all(|m| m.reused == 0));
+
+        let dir = std::env::temp_dir().join(format!("mcts_playout_log_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        write_playout_log(&dir.join("log.tsv"), std::slice::from_ref(&playout)).unwrap();
+        let log = fs::read_to_string(dir.join("log.tsv")).unwrap();
+        assert_eq!(log.lines().count(), 1 + playout.moves.len() + 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,261 @@
+use crate::board_geometry::BoardLayout;
+use crate::pagoda::PagodaPruning;
+use crate::peg_solitaire_environment::{Bitboard, MultiJump, Point};
+use crate::problem::{Problem, Target};
+use crate::solver::PositionHasher;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+
+/// Groups of cells which can't be emptied by a peg from outside while they are full: every line of a jump
+/// over one of their cells has another cell of the group at one of its ends. So the first peg to leave a
+/// full group is removed by a move which starts inside it, and disjoint full groups need different moves.
+/// The groups are the corners, which can't be jumped over at all, pairs of cells along the edges and
+/// squares of four cells.
+#[derive(Clone, Debug)]
+pub struct MersonRegions {
+    /// the bits of the cells of every group, corners first
+    pub regions: Vec<u128>,
+    // a single peg may stay in one corner if the target is any single peg
+    finishing_corner: bool,
+}
+
+impl MersonRegions {
+    pub fn new(layout: &BoardLayout, target: &Target) -> Self {
+        let bits = |cells: &[Point]| cells.iter().fold(0, |bits, p| bits | Bitboard::bit(*p));
+        let mut candidates: Vec<Vec<Point>> = layout.cells.iter().map(|p| vec![*p]).collect();
+        for p in layout.cells.iter() {
+            for d in [Point { x: 1, y: 0 }, Point { x: 0, y: 1 }] {
+                candidates.push(vec![*p, *p + d]);
+            }
+        }
+        for p in layout.cells.iter() {
+            let (right, down) = (Point { x: 1, y: 0 }, Point { x: 0, y: 1 });
+            candidates.push(vec![*p, *p + right, *p + down, *p + right + down]);
+        }
+        let regions = candidates
+            .into_iter()
+            .filter(|cells| cells.iter().all(|p| layout.is_valid(*p)) && Self::is_merson_region(layout, cells))
+            .map(|cells| bits(&cells))
+            .filter(|region| match target {
+                Target::Board(board) => board.0 & region != *region,
+                Target::SinglePegAt(p) => *region != Bitboard::bit(*p),
+                Target::AnySinglePeg => true,
+            })
+            .collect();
+        MersonRegions { regions, finishing_corner: *target == Target::AnySinglePeg }
+    }
+
+    fn is_merson_region(layout: &BoardLayout, cells: &[Point]) -> bool {
+        cells.iter().all(|c| {
+            layout.jumps.iter().all(|jump| {
+                let (from, to) = (*c - jump.offset(), *c + jump.offset());
+                !layout.is_valid(from) || !layout.is_valid(to) || cells.contains(&from) || cells.contains(&to)
+            })
+        })
+    }
+
+    /// the number of disjoint full groups which are picked one after the other, each of them needs a move
+    pub fn lower_bound(&self, board: Bitboard) -> u32 {
+        let mut used = 0;
+        let mut count = 0;
+        let mut corners = 0;
+        for region in self.regions.iter() {
+            if board.0 & region == *region && used & region == 0 {
+                used |= region;
+                count += 1;
+                corners += (region.count_ones() == 1) as u32;
+            }
+        }
+        match self.finishing_corner && corners > 0 {
+            true => count - 1,
+            false => count,
+        }
+    }
+}
+
+/// Counters of a search for the fewest moves.
+#[derive(Clone, Debug, Default)]
+pub struct MinMoveStats {
+    pub nodes: u64,
+    /// the bound on the number of moves of every iteration together with the positions it expanded
+    pub iterations: Vec<(u32, u64)>,
+    pub pagodas: PagodaPruning,
+    /// why the problem was rejected without searching
+    pub infeasible: Option<String>,
+}
+
+/// Iterative deepening A* for the solution with the fewest moves, where a move is one peg jumping as often
+/// as it likes. The number of full corners and other Merson regions never overestimates the moves left.
+pub fn solve_min_moves(problem: &Problem) -> (Option<Vec<MultiJump>>, MinMoveStats) {
+    let mut search = IterativeDeepening {
+        problem,
+        symmetries: problem.symmetries(),
+        regions: MersonRegions::new(problem.layout(), &problem.target),
+        pagodas: PagodaPruning::for_problem(problem),
+        failed: HashMap::default(),
+        moves: Vec::new(),
+        nodes: 0,
+    };
+    if let Err(reason) = problem.check_feasible() {
+        return (None, MinMoveStats { infeasible: Some(reason), ..Default::default() });
+    }
+    let mut iterations = Vec::new();
+    let mut bound = search.lower_bound(problem.start.board);
+    let moves_to_solve = problem.moves_to_solve().unwrap_or(0);
+    let solved = loop {
+        let nodes = search.nodes;
+        let solved = search.search(problem.start.board, bound);
+        iterations.push((bound, search.nodes - nodes));
+        // a solution never needs more moves than jumps
+        if solved || bound >= moves_to_solve {
+            break solved;
+        }
+        bound += 1;
+    };
+    let stats = MinMoveStats { nodes: search.nodes, iterations, pagodas: search.pagodas, infeasible: None };
+    match solved {
+        true => (Some(search.moves), stats),
+        false => (None, stats),
+    }
+}
+
+struct IterativeDeepening<'a> {
+    problem: &'a Problem,
+    symmetries: Vec<usize>,
+    regions: MersonRegions,
+    pagodas: PagodaPruning,
+    // the largest number of moves with which a position was shown to not reach the target
+    failed: HashMap<u64, u32, BuildHasherDefault<PositionHasher>>,
+    moves: Vec<MultiJump>,
+    nodes: u64,
+}
+
+impl IterativeDeepening<'_> {
+    fn lower_bound(&self, board: Bitboard) -> u32 {
+        match self.problem.is_solved(board) {
+            true => 0,
+            false => self.regions.lower_bound(board).max(1),
+        }
+    }
+
+    // whether the target can be reached from the position with at most `budget` moves
+    fn search(&mut self, board: Bitboard, budget: u32) -> bool {
+        if self.problem.is_solved(board) {
+            return true;
+        }
+        if board.count_pegs() <= self.problem.target.pegs() || self.lower_bound(board) > budget {
+            return false;
+        }
+        let layout = self.problem.layout();
+        let key = layout.compress(layout.canonical_key_among(board, &self.symmetries).board());
+        if self.failed.get(&key).is_some_and(|failed| *failed >= budget) || self.pagodas.prunes(board) {
+            return false;
+        }
+        self.nodes += 1;
+        for (multi_jump, next) in layout.multi_jumps(board) {
+            self.moves.push(multi_jump);
+            if self.search(next, budget - 1) {
+                return true;
+            }
+            self.moves.pop();
+        }
+        self.failed.insert(key, budget);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::Triangle;
+    use crate::peg_solitaire_environment::{Jump, Solitaire};
+    use crate::test_problems::english_endgame;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    // the fewest moves by a breadth first search over all positions
+    fn fewest_moves(problem: &Problem) -> Option<u32> {
+        let layout = problem.layout();
+        let mut seen = HashSet::from([problem.start.board]);
+        let mut layer = vec![problem.start.board];
+        let mut moves = 0;
+        while !layer.is_empty() {
+            if layer.iter().any(|board| problem.is_solved(*board)) {
+                return Some(moves);
+            }
+            let mut next = Vec::new();
+            for board in layer {
+                for (_, child) in layout.multi_jumps(board) {
+                    if seen.insert(child) {
+                        next.push(child);
+                    }
+                }
+            }
+            layer = next;
+            moves += 1;
+        }
+        None
+    }
+
+    fn assert_solves(problem: &Problem, solution: &[MultiJump]) {
+        let mut env = problem.new_game();
+        for multi_jump in solution {
+            assert!(env.multi_jumps().contains(multi_jump));
+            assert_eq!(env.take_multi_jump(multi_jump), multi_jump.jumps.len() as f64);
+        }
+        assert!(problem.is_solved(env.board));
+    }
+
+    #[test]
+    fn test_multi_jumps() {
+        // the peg on the left can jump into the center and on over the peg below it
+        let layout = BoardLayout::english();
+        let board = Bitboard([(1, 3), (2, 3), (3, 4)].iter().fold(0, |bits, (x, y)| bits | Bitboard::bit(Point { x: *x, y: *y })));
+        let env = Solitaire::from_layout(layout, board);
+        assert_eq!(env.actions().unwrap().len(), 2);
+        let moves = env.multi_jumps();
+        assert_eq!(moves.len(), 3);
+        let double = MultiJump { point: Point { x: 1, y: 3 }, jumps: vec![Jump::Right, Jump::Down] };
+        assert!(moves.contains(&double));
+        assert_eq!(double.end(), Point { x: 3, y: 5 });
+        let mut env = env;
+        assert_eq!(env.take_multi_jump(&double), 2.);
+        assert_eq!(env.pegs(), vec![Point { x: 3, y: 5 }]);
+    }
+
+    #[test]
+    fn test_merson_regions() {
+        let layout = BoardLayout::english();
+        let regions = MersonRegions::new(&layout, &Target::SinglePegAt(Point { x: 3, y: 3 }));
+        // the eight corners of the cross can't be jumped over
+        assert_eq!(regions.regions.iter().filter(|r| r.count_ones() == 1).count(), 8);
+        // all corners and the four full squares around the center
+        assert_eq!(regions.lower_bound(layout.start_position()), 8 + 4);
+        assert_eq!(regions.lower_bound(Bitboard(Bitboard::bit(Point { x: 2, y: 0 }))), 1);
+        let any = MersonRegions::new(&layout, &Target::AnySinglePeg);
+        assert_eq!(any.lower_bound(Bitboard(Bitboard::bit(Point { x: 2, y: 0 }))), 0);
+    }
+
+    #[test]
+    fn test_min_moves_triangle() {
+        let layout = Arc::new(BoardLayout::new(&Triangle::CRACKER_BARREL));
+        for survivor in [Point { x: 0, y: 0 }, Point { x: 2, y: 3 }] {
+            let problem = Problem::single_vacancy(layout.clone(), Point { x: 0, y: 0 }, survivor);
+            let (solution, stats) = solve_min_moves(&problem);
+            assert_eq!(solution.as_ref().map(|s| s.len() as u32), fewest_moves(&problem));
+            if let Some(solution) = solution {
+                assert_solves(&problem, &solution);
+                assert_eq!(stats.iterations.last().unwrap().0, solution.len() as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_min_moves_english_endgame() {
+        let problem = english_endgame();
+        let (solution, _) = solve_min_moves(&problem);
+        let solution = solution.unwrap();
+        assert_eq!(Some(solution.len() as u32), fewest_moves(&problem));
+        assert_solves(&problem, &solution);
+    }
+}
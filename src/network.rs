@@ -0,0 +1,485 @@
+use crate::board_geometry::BoardLayout;
+use crate::mcts::Policy;
+use crate::peg_solitaire_environment::{Bitboard, SolitaireAction};
+use crate::problem::Problem;
+use crate::state_function::ValueFunction;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 6] = b"PEGNN1";
+
+/// A position with what the network should learn for it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub board: Bitboard,
+    /// the rewards still to come
+    pub value: f64,
+    /// the probability of every legal jump, the ones left out have probability zero
+    pub policy: Vec<(SolitaireAction, f64)>,
+}
+
+/// How the gradient changes the parameters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    Sgd { learning_rate: f64 },
+    Adam { learning_rate: f64, beta1: f64, beta2: f64, epsilon: f64 },
+}
+
+/// A gradient descent optimizer together with the moments Adam keeps for every parameter.
+#[derive(Clone, Debug)]
+pub struct Optimizer {
+    pub method: Method,
+    steps: u64,
+    first: Vec<f64>,
+    second: Vec<f64>,
+}
+
+impl Optimizer {
+    pub fn sgd(learning_rate: f64) -> Self {
+        Optimizer { method: Method::Sgd { learning_rate }, steps: 0, first: Vec::new(), second: Vec::new() }
+    }
+
+    /// Adam with the usual betas of 0.9 and 0.999
+    pub fn adam(learning_rate: f64) -> Self {
+        let method = Method::Adam { learning_rate, beta1: 0.9, beta2: 0.999, epsilon: 1e-8 };
+        Optimizer { method, steps: 0, first: Vec::new(), second: Vec::new() }
+    }
+
+    pub fn step(&mut self, params: &mut [f64], gradient: &[f64]) {
+        self.steps += 1;
+        match self.method {
+            Method::Sgd { learning_rate } => {
+                for (p, g) in params.iter_mut().zip(gradient) {
+                    *p -= learning_rate * g;
+                }
+            }
+            Method::Adam { learning_rate, beta1, beta2, epsilon } => {
+                if self.first.len() != params.len() {
+                    self.first = vec![0.; params.len()];
+                    self.second = vec![0.; params.len()];
+                }
+                let (correction1, correction2) = (1. - beta1.powi(self.steps as i32), 1. - beta2.powi(self.steps as i32));
+                for (idx, (p, g)) in params.iter_mut().zip(gradient).enumerate() {
+                    self.first[idx] = beta1 * self.first[idx] + (1. - beta1) * g;
+                    self.second[idx] = beta2 * self.second[idx] + (1. - beta2) * g * g;
+                    *p -= learning_rate * (self.first[idx] / correction1) / ((self.second[idx] / correction2).sqrt() + epsilon);
+                }
+            }
+        }
+    }
+}
+
+/// The outputs of the network for a position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    pub value: f64,
+    /// one logit for every jump of `Network::actions`
+    pub logits: Vec<f64>,
+}
+
+// a fully connected layer, its weights are stored row by row in the parameters followed by the biases
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Dense {
+    offset: usize,
+    inputs: usize,
+    outputs: usize,
+}
+
+impl Dense {
+    fn forward(&self, params: &[f64], input: &[f64]) -> Vec<f64> {
+        let weights = &params[self.offset..self.offset + self.inputs * self.outputs];
+        let bias = &params[self.offset + self.inputs * self.outputs..self.offset + self.len()];
+        (0..self.outputs)
+            .map(|o| bias[o] + weights[o * self.inputs..(o + 1) * self.inputs].iter().zip(input).map(|(w, x)| w * x).sum::<f64>())
+            .collect()
+    }
+
+    // add the gradient of the parameters to `gradient` and return the gradient of the input
+    fn backward(&self, params: &[f64], input: &[f64], output_gradient: &[f64], gradient: &mut [f64]) -> Vec<f64> {
+        let mut input_gradient = vec![0.; self.inputs];
+        for (o, g) in output_gradient.iter().enumerate() {
+            if *g == 0. {
+                continue;
+            }
+            let row = self.offset + o * self.inputs;
+            for i in 0..self.inputs {
+                gradient[row + i] += g * input[i];
+                input_gradient[i] += g * params[row + i];
+            }
+            gradient[self.offset + self.inputs * self.outputs + o] += g;
+        }
+        input_gradient
+    }
+
+    fn len(&self) -> usize {
+        (self.inputs + 1) * self.outputs
+    }
+}
+
+/// A small multilayer perceptron on the CPU. The input is one bit per cell of the board, the hidden layers
+/// use ReLU, and there are two heads: the value of the position and a logit for every jump of the board,
+/// 76 on the English board. The policy is the softmax of the logits of the legal jumps.
+#[derive(Clone, Debug)]
+pub struct Network {
+    pub layout: Arc<BoardLayout>,
+    /// the sizes of the hidden layers
+    pub hidden: Vec<usize>,
+    /// every jump which is possible on some position of the board, in the order of the policy head
+    pub actions: Vec<SolitaireAction>,
+    pub params: Vec<f64>,
+    action_index: HashMap<SolitaireAction, usize>,
+    layers: Vec<Dense>,
+    value_head: Dense,
+    policy_head: Dense,
+}
+
+impl Network {
+    /// a network with He initialized weights and zero biases
+    pub fn new(layout: Arc<BoardLayout>, hidden: &[usize], seed: u64) -> Self {
+        let mut network = Network::with_zeros(layout, hidden);
+        let mut rng = StdRng::seed_from_u64(seed);
+        for layer in network.layers.iter().chain([&network.value_head, &network.policy_head]) {
+            let scale = (2. / layer.inputs as f64).sqrt();
+            for w in network.params[layer.offset..layer.offset + layer.inputs * layer.outputs].iter_mut() {
+                *w = scale * (rng.gen::<f64>() * 2. - 1.) * 3f64.sqrt();
+            }
+        }
+        network
+    }
+
+    fn with_zeros(layout: Arc<BoardLayout>, hidden: &[usize]) -> Self {
+        let all = Bitboard(layout.cells.iter().fold(0, |bits, p| bits | Bitboard::bit(*p)));
+        // every jump whose three cells are on the board
+        let actions: Vec<SolitaireAction> = layout
+            .cells
+            .iter()
+            .flat_map(|p| layout.jumps.iter().map(|jump| SolitaireAction { point: *p, action: *jump }))
+            .filter(|a| {
+                let over = a.point + a.action.offset();
+                layout.is_valid(over) && layout.is_valid(over + a.action.offset()) && all.is_peg(a.point)
+            })
+            .collect();
+        let action_index = actions.iter().enumerate().map(|(idx, a)| (*a, idx)).collect();
+        let mut offset = 0;
+        let mut inputs = layout.cells.len();
+        let mut layers = Vec::new();
+        for outputs in hidden.iter().copied() {
+            layers.push(Dense { offset, inputs, outputs });
+            offset += (inputs + 1) * outputs;
+            inputs = outputs;
+        }
+        let value_head = Dense { offset, inputs, outputs: 1 };
+        offset += value_head.len();
+        let policy_head = Dense { offset, inputs, outputs: actions.len() };
+        offset += policy_head.len();
+        Network { layout, hidden: hidden.to_vec(), actions, params: vec![0.; offset], action_index, layers, value_head, policy_head }
+    }
+
+    /// the number of cells of the board, which is the number of inputs
+    pub fn inputs(&self) -> usize {
+        self.layout.cells.len()
+    }
+
+    pub fn encode(&self, board: Bitboard) -> Vec<f64> {
+        self.layout.cells.iter().map(|p| board.is_peg(*p) as u8 as f64).collect()
+    }
+
+    pub fn action_index(&self, action: SolitaireAction) -> Option<usize> {
+        self.action_index.get(&action).copied()
+    }
+
+    // the activations of every hidden layer, starting with the input
+    fn hidden_activations(&self, board: Bitboard) -> Vec<Vec<f64>> {
+        let mut activations = vec![self.encode(board)];
+        for layer in self.layers.iter() {
+            let z = layer.forward(&self.params, activations.last().unwrap());
+            activations.push(z.into_iter().map(|z| z.max(0.)).collect());
+        }
+        activations
+    }
+
+    pub fn forward(&self, board: Bitboard) -> Output {
+        let activations = self.hidden_activations(board);
+        let last = activations.last().unwrap();
+        Output { value: self.value_head.forward(&self.params, last)[0], logits: self.policy_head.forward(&self.params, last) }
+    }
+
+    /// the softmax of the logits of the actions, in the same order
+    pub fn policy(&self, board: Bitboard, actions: &[SolitaireAction]) -> Vec<f64> {
+        softmax(&self.forward(board).logits, &self.indices(actions))
+    }
+
+    fn indices(&self, actions: &[SolitaireAction]) -> Vec<usize> {
+        actions.iter().map(|a| self.action_index(*a).expect("the jump is not on the board")).collect()
+    }
+
+    // the gradient of all parameters given the gradients of the two heads
+    fn backward(&self, activations: &[Vec<f64>], value_gradient: f64, logit_gradient: &[f64]) -> Vec<f64> {
+        let mut gradient = vec![0.; self.params.len()];
+        let last = activations.last().unwrap();
+        let mut hidden_gradient = self.value_head.backward(&self.params, last, &[value_gradient], &mut gradient);
+        let from_policy = self.policy_head.backward(&self.params, last, logit_gradient, &mut gradient);
+        hidden_gradient.iter_mut().zip(from_policy).for_each(|(g, p)| *g += p);
+        for (idx, layer) in self.layers.iter().enumerate().rev() {
+            // the derivative of ReLU is one where the activation is positive
+            let output_gradient: Vec<f64> =
+                hidden_gradient.iter().zip(activations[idx + 1].iter()).map(|(g, a)| if *a > 0. { *g } else { 0. }).collect();
+            hidden_gradient = layer.backward(&self.params, &activations[idx], &output_gradient, &mut gradient);
+        }
+        gradient
+    }
+
+    /// The loss of the sample, half the squared error of the value plus the cross entropy of the policy, and its
+    /// gradient by every parameter.
+    pub fn loss_and_gradient(&self, sample: &Sample) -> (f64, Vec<f64>) {
+        let activations = self.hidden_activations(sample.board);
+        let last = activations.last().unwrap();
+        let value = self.value_head.forward(&self.params, last)[0];
+        let logits = self.policy_head.forward(&self.params, last);
+        let error = value - sample.value;
+        let mut loss = 0.5 * error * error;
+        let mut logit_gradient = vec![0.; self.actions.len()];
+        if !sample.policy.is_empty() {
+            let legal = self.indices(&self.layout.actions(sample.board));
+            let probabilities = softmax(&logits, &legal);
+            for (idx, p) in legal.iter().zip(probabilities) {
+                logit_gradient[*idx] = p;
+            }
+            for (action, target) in sample.policy.iter() {
+                let idx = self.action_index(*action).unwrap();
+                loss -= target * logit_gradient[idx].max(1e-12).ln();
+                logit_gradient[idx] -= target;
+            }
+        }
+        (loss, self.backward(&activations, error, &logit_gradient))
+    }
+
+    /// One step of the optimizer on the average gradient of the samples, returns their average loss.
+    pub fn train_batch(&mut self, samples: &[Sample], optimizer: &mut Optimizer) -> f64 {
+        let mut gradient = vec![0.; self.params.len()];
+        let mut loss = 0.;
+        for sample in samples {
+            let (l, g) = self.loss_and_gradient(sample);
+            loss += l;
+            gradient.iter_mut().zip(g).for_each(|(total, g)| *total += g / samples.len() as f64);
+        }
+        optimizer.step(&mut self.params, &gradient);
+        loss / samples.len() as f64
+    }
+
+    /// the sizes of the layers and the parameters, little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        let sizes = [self.inputs(), self.actions.len(), self.hidden.len()].into_iter().chain(self.hidden.iter().copied());
+        for size in sizes {
+            bytes.extend((size as u32).to_le_bytes());
+        }
+        for p in self.params.iter() {
+            bytes.extend(p.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// read a network written by `to_bytes` for the board
+    pub fn from_bytes(bytes: &[u8], layout: Arc<BoardLayout>) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err("not a network file".into());
+        }
+        let mut words = bytes[MAGIC.len()..].chunks_exact(4).map(|c| u32::from_le_bytes(c.try_into().unwrap()) as usize);
+        let mut next = || words.next().ok_or("the file ends too early");
+        let (inputs, actions, layers) = (next()?, next()?, next()?);
+        let hidden = (0..layers).map(|_| next()).collect::<Result<Vec<usize>, _>>()?;
+        // the parameters the header asks for, checked against the rest of the file before anything is allocated
+        let sizes: Vec<usize> = std::iter::once(inputs).chain(hidden.iter().copied()).collect();
+        let last = *sizes.last().unwrap();
+        let expected = sizes
+            .windows(2)
+            .map(|w| (w[0] + 1).checked_mul(w[1]))
+            .chain([Some(last + 1), (last + 1).checked_mul(actions)])
+            .try_fold(0usize, |total, len| total.checked_add(len?))
+            .ok_or("the header asks for more parameters than fit into memory")?;
+        let start = MAGIC.len() + 4 * (3 + layers);
+        let found = bytes.len() - start;
+        if expected.checked_mul(8) != Some(found) {
+            return Err(format!("expected {} parameters but found {} bytes of them", expected, found).into());
+        }
+        let mut network = Network::with_zeros(layout, &hidden);
+        if inputs != network.inputs() || actions != network.actions.len() {
+            return Err(format!("the network has {} inputs and {} jumps, which doesn't fit the board", inputs, actions).into());
+        }
+        network.params = bytes[start..].chunks_exact(8).map(|c| f64::from_le_bytes(c.try_into().unwrap())).collect();
+        Ok(network)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(fs::write(path, self.to_bytes())?)
+    }
+
+    pub fn load(path: &Path, layout: Arc<BoardLayout>) -> Result<Self, Box<dyn Error>> {
+        Network::from_bytes(&fs::read(path)?, layout)
+    }
+}
+
+// the softmax over the logits with the given indices
+fn softmax(logits: &[f64], indices: &[usize]) -> Vec<f64> {
+    let max = indices.iter().map(|idx| logits[*idx]).fold(f64::NEG_INFINITY, f64::max);
+    let exps: Vec<f64> = indices.iter().map(|idx| (logits[*idx] - max).exp()).collect();
+    let sum: f64 = exps.iter().sum();
+    exps.into_iter().map(|e| e / sum).collect()
+}
+
+impl ValueFunction for Network {
+    fn name(&self) -> String {
+        format!("network {:?}", self.hidden)
+    }
+
+    fn value(&self, board: Bitboard) -> f64 {
+        self.forward(board).value
+    }
+
+    fn gradient(&self, board: Bitboard) -> Vec<(u64, f64)> {
+        let gradient = self.backward(&self.hidden_activations(board), 1., &vec![0.; self.actions.len()]);
+        gradient.into_iter().enumerate().filter(|(_, g)| *g != 0.).map(|(idx, g)| (idx as u64, g)).collect()
+    }
+
+    fn add_to_parameter(&mut self, parameter: u64, delta: f64) {
+        self.params[parameter as usize] += delta;
+    }
+}
+
+impl Policy for Network {
+    fn name(&self) -> String {
+        format!("network {:?}", self.hidden)
+    }
+
+    fn priors(&self, _problem: &Problem, board: Bitboard, actions: &[SolitaireAction]) -> Vec<f64> {
+        self.policy(board, actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::Triangle;
+    use crate::mcts::{Mcts, MctsConfig, Rollout, Selection};
+    use crate::q_learning::EpsilonSchedule;
+    use crate::td_learning::{TdConfig, TdLearner};
+    use crate::test_problems::triangle;
+
+    fn sample(network: &Network, board: Bitboard) -> Sample {
+        let actions = network.layout.actions(board);
+        let policy = actions.iter().enumerate().map(|(idx, a)| (*a, if idx == 0 { 0.7 } else { 0.3 / (actions.len() - 1) as f64 })).collect();
+        Sample { board, value: 3., policy }
+    }
+
+    #[test]
+    fn test_english_network() {
+        let network = Network::new(BoardLayout::english(), &[16], 0);
+        assert_eq!((network.inputs(), network.actions.len()), (33, 76));
+        assert_eq!(network.params.len(), 34 * 16 + 17 + 17 * 76);
+        let board = Problem::central_game().start.board;
+        let actions = network.layout.actions(board);
+        let policy = network.policy(board, &actions);
+        assert_eq!(policy.len(), 4);
+        assert!((policy.iter().sum::<f64>() - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_gradient_matches_finite_differences() {
+        let mut network = Network::new(BoardLayout::english(), &[8, 6], 1);
+        let problem = Problem::central_game();
+        let board = problem.layout().apply(problem.start.board, problem.layout().actions(problem.start.board)[0]);
+        let sample = sample(&network, board);
+        let (_, gradient) = network.loss_and_gradient(&sample);
+        let h = 1e-6;
+        for idx in (0..network.params.len()).step_by(7) {
+            let original = network.params[idx];
+            network.params[idx] = original + h;
+            let up = network.loss_and_gradient(&sample).0;
+            network.params[idx] = original - h;
+            let down = network.loss_and_gradient(&sample).0;
+            network.params[idx] = original;
+            assert!(((up - down) / (2. * h) - gradient[idx]).abs() < 1e-5, "parameter {}", idx);
+        }
+        // the gradient of the value alone
+        let value_gradient: HashMap<u64, f64> = ValueFunction::gradient(&network, board).into_iter().collect();
+        for idx in (0..network.params.len()).step_by(5) {
+            let original = network.params[idx];
+            network.params[idx] = original + h;
+            let up = network.value(board);
+            network.params[idx] = original - h;
+            let down = network.value(board);
+            network.params[idx] = original;
+            let expected = value_gradient.get(&(idx as u64)).copied().unwrap_or(0.);
+            assert!(((up - down) / (2. * h) - expected).abs() < 1e-6, "parameter {}", idx);
+        }
+    }
+
+    #[test]
+    fn test_optimizers_fit_samples() {
+        let problem = Problem::central_game();
+        let layout = problem.layout();
+        let mut board = problem.start.board;
+        let mut samples = Vec::new();
+        for value in 0..5 {
+            let network = Network::new(layout.clone(), &[16], 0);
+            samples.push(Sample { value: value as f64, ..sample(&network, board) });
+            board = layout.apply(board, layout.actions(board)[0]);
+        }
+        for mut optimizer in [Optimizer::sgd(0.02), Optimizer::adam(0.01)] {
+            let mut network = Network::new(layout.clone(), &[16], 0);
+            let first = network.train_batch(&samples, &mut optimizer);
+            let mut last = first;
+            for _ in 0..300 {
+                last = network.train_batch(&samples, &mut optimizer);
+            }
+            assert!(last < first / 2., "{:?}: {} {}", optimizer.method, first, last);
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let network = Network::new(BoardLayout::english(), &[8, 4], 2);
+        let path = std::env::temp_dir().join(format!("network_save_and_load_{}.bin", std::process::id()));
+        network.save(&path).unwrap();
+        let loaded = Network::load(&path, BoardLayout::english()).unwrap();
+        let board = Problem::central_game().start.board;
+        assert_eq!(loaded.forward(board), network.forward(board));
+        assert_eq!(loaded.hidden, vec![8, 4]);
+        fs::remove_file(&path).unwrap();
+
+        let triangle = Arc::new(BoardLayout::new(&Triangle::CRACKER_BARREL));
+        assert!(Network::from_bytes(&network.to_bytes(), triangle).is_err());
+        assert!(Network::from_bytes(&network.to_bytes()[..40], BoardLayout::english()).is_err());
+        // a header which asks for huge layers is rejected before they are allocated
+        let header = |hidden: &[u32]| {
+            let mut bytes = MAGIC.to_vec();
+            for word in [33, network.actions.len() as u32, hidden.len() as u32].iter().chain(hidden) {
+                bytes.extend(word.to_le_bytes());
+            }
+            Network::from_bytes(&bytes, BoardLayout::english()).unwrap_err().to_string()
+        };
+        assert!(header(&[u32::MAX]).contains("expected"));
+        assert!(header(&[u32::MAX, u32::MAX]).contains("more parameters"));
+    }
+
+    #[test]
+    fn test_network_in_agents() {
+        let problem = triangle();
+        let layout = problem.layout().clone();
+        let config = TdConfig { alpha: 0.001, lambda: 0.5, episodes: 300, epsilon: EpsilonSchedule::Constant(0.2), ..Default::default() };
+        let mut learner = TdLearner::with_values(&problem, config, Network::new(layout.clone(), &[16], 0));
+        learner.train();
+        assert!(learner.values.params.iter().all(|p| p.is_finite()));
+
+        let config = MctsConfig { iterations: 200, selection: Selection::Puct, ..Default::default() };
+        let mut mcts = Mcts::new(&problem, config, Box::new(learner.values.clone()), Rollout::Random).unwrap();
+        let playout = mcts.play();
+        assert!(playout.moves.len() <= 13 && !playout.moves.is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+use crate::board_geometry::BoardLayout;
+use crate::peg_solitaire_environment::{Bitboard, Point};
+use crate::problem::{Problem, Target};
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// A weighting of the cells which never increases under a jump: the weight of the cell a peg lands on is
+/// at most the sum of the weights of the two cells it leaves. So the weighted sum of the pegs of a
+/// position can't grow, and a position whose sum is below the sum of the target can't reach it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pagoda {
+    pub name: String,
+    // the weight of every bit of the bitboard
+    weights: Vec<i32>,
+}
+
+// the classic pagoda functions of the English board, drawn like the board
+const ENGLISH_PAGODAS: [(&str, &str); 3] = [
+    (
+        "corners",
+        "
+        . . -1  1 -1 . .
+        . .  1  0  1 . .
+        -1 1  0  1  0 1 -1
+        1  0  1  1  1 0  1
+        -1 1  0  1  0 1 -1
+        . .  1  0  1 . .
+        . . -1  1 -1 . .",
+    ),
+    (
+        "cross",
+        "
+        . . 0 0 0 . .
+        . . 0 1 0 . .
+        0 0 0 0 0 0 0
+        0 1 0 1 0 1 0
+        0 0 0 0 0 0 0
+        . . 0 1 0 . .
+        . . 0 0 0 . .",
+    ),
+    (
+        // the Fibonacci numbers growing towards the bottom edge
+        "fibonacci",
+        "
+        . . 0 0 0 . .
+        . . 1 1 1 . .
+        1 1 1 1 1 1 1
+        2 2 2 2 2 2 2
+        3 3 3 3 3 3 3
+        . . 5 5 5 . .
+        . . 8 8 8 . .",
+    ),
+];
+
+impl Pagoda {
+    pub fn new(name: &str, weights: &[(Point, i32)]) -> Self {
+        let mut pagoda = Pagoda { name: String::from(name), weights: vec![0; 128] };
+        for (p, weight) in weights {
+            pagoda.weights[Bitboard::index(*p)] = *weight;
+        }
+        pagoda
+    }
+
+    /// Read the weights from a grid with one line per row of the board. Every cell of the board gets an
+    /// integer and every other place a `.`, lines which are empty or start with `#` are skipped.
+    pub fn parse(name: &str, grid: &str, layout: &BoardLayout) -> Result<Self, String> {
+        let mut weights = Vec::new();
+        let rows = grid.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for (y, row) in rows.enumerate() {
+            for (x, token) in row.split_whitespace().enumerate() {
+                let p = Point { x: x as i32, y: y as i32 };
+                match (token, layout.is_valid(p)) {
+                    (".", false) => (),
+                    (".", true) => return Err(format!("{:?} is a cell of the board but has no weight", p)),
+                    (_, false) => return Err(format!("{:?} has a weight but is no cell of the board", p)),
+                    (_, true) => weights.push((p, token.parse().map_err(|_| format!("{} is no weight", token))?)),
+                }
+            }
+        }
+        if weights.len() != layout.cells.len() {
+            return Err(format!("expected {} weights but found {}", layout.cells.len(), weights.len()));
+        }
+        let pagoda = Pagoda::new(name, &weights);
+        match pagoda.violation(layout) {
+            Some((from, over, to)) => Err(format!(
+                "{} is no pagoda function, the jump from {:?} over {:?} to {:?} increases it",
+                name, from, over, to
+            )),
+            None => Ok(pagoda),
+        }
+    }
+
+    /// load the weights from a file in the format of `parse`, the name of the pagoda is the name of the file
+    pub fn load(path: &Path, layout: &BoardLayout) -> Result<Self, Box<dyn Error>> {
+        let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        Ok(Pagoda::parse(&name, &fs::read_to_string(path)?, layout)?)
+    }
+
+    /// the classic pagodas of the English board together with all their symmetric images
+    pub fn english() -> Vec<Pagoda> {
+        let layout = BoardLayout::english();
+        ENGLISH_PAGODAS
+            .iter()
+            .flat_map(|(name, grid)| Pagoda::parse(name, grid, &layout).unwrap().symmetric_images(&layout))
+            .collect()
+    }
+
+    /// the pagoda under every symmetry of the board, leaving out duplicates
+    pub fn symmetric_images(&self, layout: &BoardLayout) -> Vec<Pagoda> {
+        let mut images: Vec<Pagoda> = Vec::new();
+        for (idx, t) in layout.symmetries.iter().enumerate() {
+            let weights: Vec<(Point, i32)> = layout.cells.iter().map(|p| (t.apply(*p), self.weight(*p))).collect();
+            let name = match idx {
+                0 => self.name.clone(),
+                _ => format!("{} {}", self.name, idx),
+            };
+            let image = Pagoda::new(&name, &weights);
+            if images.iter().all(|other| other.weights != image.weights) {
+                images.push(image);
+            }
+        }
+        images
+    }
+
+    pub fn weight(&self, p: Point) -> i32 {
+        self.weights[Bitboard::index(p)]
+    }
+
+    pub fn value(&self, board: Bitboard) -> i32 {
+        let mut bits = board.0;
+        let mut value = 0;
+        while bits != 0 {
+            value += self.weights[bits.trailing_zeros() as usize];
+            bits &= bits - 1;
+        }
+        value
+    }
+
+    /// the smallest value a position which reaches the target can have
+    pub fn target_value(&self, target: &Target, layout: &BoardLayout) -> i32 {
+        match target {
+            Target::SinglePegAt(p) => self.weight(*p),
+            Target::Board(board) => self.value(*board),
+            Target::AnySinglePeg => layout.cells.iter().map(|p| self.weight(*p)).min().unwrap_or(0),
+        }
+    }
+
+    /// a jump on the board which increases the weighted sum, if there is one
+    pub fn violation(&self, layout: &BoardLayout) -> Option<(Point, Point, Point)> {
+        for from in layout.cells.iter() {
+            for jump in layout.jumps.iter() {
+                let over = *from + jump.offset();
+                let to = over + jump.offset();
+                if layout.is_valid(over) && layout.is_valid(to) && self.weight(to) > self.weight(*from) + self.weight(over) {
+                    return Some((*from, over, to));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Pagoda functions which are checked one after the other to prove that the target of a problem can't be
+/// reached, counting how many positions each one ruled out.
+#[derive(Clone, Debug, Default)]
+pub struct PagodaPruning {
+    pub pagodas: Vec<Pagoda>,
+    /// the value of the target for every pagoda
+    pub target_values: Vec<i32>,
+    /// the number of positions ruled out by every pagoda
+    pub pruned: Vec<u64>,
+    pub checked: u64,
+}
+
+impl PagodaPruning {
+    pub fn new(pagodas: Vec<Pagoda>, problem: &Problem) -> Self {
+        for pagoda in pagodas.iter() {
+            assert!(pagoda.violation(problem.layout()).is_none(), "{} is no pagoda function of the board", pagoda.name);
+        }
+        let target_values = pagodas.iter().map(|p| p.target_value(&problem.target, problem.layout())).collect();
+        let pruned = vec![0; pagodas.len()];
+        PagodaPruning { pagodas, target_values, pruned, checked: 0 }
+    }
+
+    /// the built in pagodas which belong to the board of the problem, none for boards other than the English one
+    pub fn for_problem(problem: &Problem) -> Self {
+        let english = problem.layout().cells == BoardLayout::english().cells && problem.layout().jumps == BoardLayout::english().jumps;
+        match english {
+            true => PagodaPruning::new(Pagoda::english(), problem),
+            false => PagodaPruning::new(Vec::new(), problem),
+        }
+    }
+
+    /// whether one of the pagodas proves that the target can't be reached from the position
+    pub fn prunes(&mut self, board: Bitboard) -> bool {
+        self.checked += 1;
+        for (idx, pagoda) in self.pagodas.iter().enumerate() {
+            if pagoda.value(board) < self.target_values[idx] {
+                self.pruned[idx] += 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn total_pruned(&self) -> u64 {
+        self.pruned.iter().sum()
+    }
+
+    pub fn report(&self) -> String {
+        let mut s = String::new();
+        writeln!(&mut s, "Checked {} positions, {} of them were ruled out", self.checked, self.total_pruned()).unwrap();
+        for (pagoda, pruned) in self.pagodas.iter().zip(self.pruned.iter()) {
+            writeln!(&mut s, "{}: {}", pagoda.name, pruned).unwrap();
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::French;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_english_pagodas() {
+        let layout = BoardLayout::english();
+        for pagoda in Pagoda::english() {
+            assert_eq!(pagoda.violation(&layout), None);
+        }
+        let corners = &Pagoda::english()[0];
+        // the central game starts with 8 and has to finish with 1
+        assert_eq!(corners.value(layout.single_vacancy(Point { x: 3, y: 3 })), 8);
+        assert_eq!(corners.target_value(&Target::SinglePegAt(Point { x: 3, y: 3 }), &layout), 1);
+        // a single peg in a corner of the cross can't get back to the center
+        let problem = Problem::central_game();
+        let mut pruning = PagodaPruning::for_problem(&problem);
+        assert!(pruning.prunes(Bitboard(Bitboard::bit(Point { x: 2, y: 0 }))));
+        assert!(!pruning.prunes(Bitboard(Bitboard::bit(Point { x: 3, y: 1 }) | Bitboard::bit(Point { x: 3, y: 2 }))));
+        assert_eq!((pruning.checked, pruning.total_pruned()), (2, 1));
+    }
+
+    #[test]
+    fn test_every_english_pagoda_prunes() {
+        let problem = Problem::central_game();
+        let pegs = |points: &[(i32, i32)]| Bitboard(points.iter().map(|(x, y)| Bitboard::bit(Point { x: *x, y: *y })).sum());
+        // an unsolvable position for every pagoda which only it and its images rule out
+        let positions = [
+            ("corners", pegs(&[(2, 0), (1, 3)])),
+            ("cross", pegs(&[(3, 0), (0, 3)])),
+            ("fibonacci", pegs(&[(3, 0), (3, 1)])),
+        ];
+        assert_eq!(positions.len(), ENGLISH_PAGODAS.len());
+        let layout = BoardLayout::english();
+        for (name, grid) in ENGLISH_PAGODAS {
+            let images = Pagoda::parse(name, grid, &layout).unwrap().symmetric_images(&layout);
+            let mut pruning = PagodaPruning::new(images, &problem);
+            for (other, board) in positions {
+                assert_eq!(pruning.prunes(board), other == name, "{} on the position of {}", name, other);
+            }
+        }
+        assert_eq!(Pagoda::english().len(), 1 + 1 + 4);
+    }
+
+    #[test]
+    fn test_parse_pagoda() {
+        let layout = BoardLayout::english();
+        let zeros = "# all zero\n. . 0 0 0 . .\n. . 0 0 0 . .\n0 0 0 0 0 0 0\n0 0 0 0 0 0 0\n0 0 0 0 0 0 0\n. . 0 0 0 . .\n. . 0 0 0 . .";
+        assert!(Pagoda::parse("zeros", zeros, &layout).is_ok());
+        assert!(Pagoda::parse("short", zeros.rsplit_once('\n').unwrap().0, &layout).is_err());
+        assert!(Pagoda::parse("corner", &zeros.replacen(". .", "0 .", 1), &layout).is_err());
+        assert!(Pagoda::parse("letter", &zeros.replacen('0', "a", 1), &layout).is_err());
+        // a positive weight next to two zeros grows when a peg jumps onto it
+        let err = Pagoda::parse("center", &zeros.replace("0 0 0 0 0 0 0\n0 0 0 0", "0 0 0 0 0 0 0\n0 0 0 1"), &layout).unwrap_err();
+        assert!(err.contains("no pagoda function"));
+        // the English pagodas don't fit the French board
+        let french = Arc::new(BoardLayout::new(&French));
+        assert!(Pagoda::parse("corners", ENGLISH_PAGODAS[0].1, &french).is_err());
+    }
+
+    #[test]
+    fn test_load_pagoda() {
+        // the pagoda is named after the file
+        let name = format!("corners_pagoda_{}", std::process::id());
+        let path = std::env::temp_dir().join(format!("{}.txt", name));
+        fs::write(&path, ENGLISH_PAGODAS[0].1).unwrap();
+        let pagoda = Pagoda::load(&path, &BoardLayout::english()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(pagoda.name, name);
+        assert_eq!(pagoda.weights, Pagoda::english()[0].weights);
+    }
+}
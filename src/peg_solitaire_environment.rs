@@ -1,7 +1,8 @@
 use std::fmt::{Display, Debug, Result, Formatter};
 use std::hash::Hash;
-use std::{cmp::Eq, ops::Add};
-use std::path::Path;
+use std::{cmp::Eq, ops::{Add, Sub}};
+use std::sync::Arc;
+use crate::board_geometry::{BoardGeometry, BoardLayout, RuleSet};
 
 #[derive(Clone, Copy, Eq, Hash, PartialEq, Debug, PartialOrd, Ord)]
 pub struct Point {
@@ -20,27 +21,81 @@ impl Add for Point {
     }
 }
 
+impl Sub for Point {
+    type Output = Point;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Point {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Eq, Hash, PartialEq, Debug)]
 pub enum Jump {
     Left = 0,
     Down = 1,
     Right = 2,
     Up = 3,
+    // the diagonal along which the boards with six directions are laid out
+    DownRight = 4,
+    UpLeft = 5,
+    // the other diagonal, only used by the diagonal rules
+    UpRight = 6,
+    DownLeft = 7,
 }
 
 impl Jump {
-    fn from(idx: usize) -> Self {
+    pub fn from(idx: usize) -> Self {
         match idx {
             0 => Jump::Left,
             1 => Jump::Down,
             2 => Jump::Right,
             3 => Jump::Up,
+            4 => Jump::DownRight,
+            5 => Jump::UpLeft,
+            6 => Jump::UpRight,
+            7 => Jump::DownLeft,
             num => panic!(
-                "Please initialize only in the range 0 to 3, but received {}",
+                "Please initialize only in the range 0 to 7, but received {}",
                 num
             ),
         }
     }
+
+    /// the step from the jumping peg to the peg which is jumped over
+    pub fn offset(&self) -> Point {
+        match self {
+            Jump::Left => Point { x: -1, y: 0 },
+            Jump::Down => Point { x: 0, y: 1 },
+            Jump::Right => Point { x: 1, y: 0 },
+            Jump::Up => Point { x: 0, y: -1 },
+            Jump::DownRight => Point { x: 1, y: 1 },
+            Jump::UpLeft => Point { x: -1, y: -1 },
+            Jump::UpRight => Point { x: 1, y: -1 },
+            Jump::DownLeft => Point { x: -1, y: 1 },
+        }
+    }
+
+    pub fn from_offset(offset: Point) -> Option<Self> {
+        match (offset.x, offset.y) {
+            (-1, 0) => Some(Jump::Left),
+            (0, 1) => Some(Jump::Down),
+            (1, 0) => Some(Jump::Right),
+            (0, -1) => Some(Jump::Up),
+            (1, 1) => Some(Jump::DownRight),
+            (-1, -1) => Some(Jump::UpLeft),
+            (1, -1) => Some(Jump::UpRight),
+            (-1, 1) => Some(Jump::DownLeft),
+            _ => None,
+        }
+    }
+
+    // the same step expressed as a shift on the bitboard
+    pub(crate) fn shift(&self) -> i32 {
+        let offset = self.offset();
+        offset.y * STRIDE + offset.x
+    }
 }
 
 #[derive(Clone, Copy, Eq, Hash, PartialEq, Debug)]
@@ -55,6 +110,31 @@ impl SolitaireAction {
     }
 }
 
+/// A move of the "fewest moves" problem: one peg jumping one or more times in a row.
+#[derive(Clone, Eq, Hash, PartialEq, Debug)]
+pub struct MultiJump {
+    pub point: Point,
+    pub jumps: Vec<Jump>,
+}
+
+impl MultiJump {
+    /// the single jumps of the move, one after the other
+    pub fn actions(&self) -> Vec<SolitaireAction> {
+        let mut point = self.point;
+        let mut actions = Vec::with_capacity(self.jumps.len());
+        for jump in self.jumps.iter() {
+            actions.push(SolitaireAction { point, action: *jump });
+            point = point + jump.offset() + jump.offset();
+        }
+        actions
+    }
+
+    /// the cell the peg finishes on
+    pub fn end(&self) -> Point {
+        self.jumps.iter().fold(self.point, |point, jump| point + jump.offset() + jump.offset())
+    }
+}
+
 #[derive(Clone, Copy, Eq, Hash, PartialEq, Debug)]
 pub struct SolitaireState {
     pub value: [[i32; 7]; 7],
@@ -70,19 +150,210 @@ impl SolitaireState {
     }
 }
 
-impl ToString for SolitaireState {
-    fn to_string(&self) -> String {
-        let a: String = self.value.iter().flat_map(|&s| s).filter(|&s| s!=-1).map(|s| s.to_string()).collect();
-        a
+impl Display for SolitaireState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for value in self.value.iter().flat_map(|&s| s).filter(|&s| s != -1) {
+            write!(f, "{}", value)?;
+        }
+        Ok(())
+    }
+}
+
+// the symmetries of the board: the three rotations and the four reflections
+
+pub fn rotate_90(state: &SolitaireState) -> SolitaireState {
+    let mut s90 = [[-1; 7]; 7];
+    for (row_idx, row) in state.value().iter().rev().enumerate() {
+        for (col_idx, val) in row.iter().enumerate() {
+            s90[6 - col_idx][6 - row_idx] = *val
+        }
     }
+    SolitaireState { value: s90 }
 }
 
-pub struct Solitaire {
-    pub state: SolitaireState,
-    pub holes: Vec<Point>,
-    pub pegs: Vec<Point>,
+pub fn rotate_180(state: &SolitaireState) -> SolitaireState {
+    let mut s180 = [[-1; 7]; 7];
+    for (row_idx, row) in state.value().iter().rev().enumerate() {
+        for (col_idx, val) in row.iter().enumerate() {
+            s180[row_idx][6 - col_idx] = *val
+        }
+    }
+    SolitaireState { value: s180 }
+}
+
+pub fn rotate_270(state: &SolitaireState) -> SolitaireState {
+    let mut s270 = [[-1; 7]; 7];
+    for (row_idx, row) in state.value().iter().rev().enumerate() {
+        for (col_idx, val) in row.iter().enumerate() {
+            s270[col_idx][row_idx] = *val
+        }
+    }
+    SolitaireState { value: s270 }
+}
+
+pub fn mirror(state: &SolitaireState) -> SolitaireState {
+    let mut mirrored = [[-1; 7]; 7];
+    for (row_idx, row) in state.value().iter().rev().enumerate() {
+        for (col_idx, val) in row.iter().enumerate() {
+            mirrored[row_idx][col_idx] = *val
+        }
+    }
+    SolitaireState { value: mirrored }
+}
+
+pub fn mirror_90(state: &SolitaireState) -> SolitaireState {
+    let mut mirrored90 = [[-1; 7]; 7];
+    for (row_idx, row) in state.value().iter().rev().enumerate() {
+        for (col_idx, val) in row.iter().enumerate() {
+            mirrored90[6 - col_idx][row_idx] = *val
+        }
+    }
+    SolitaireState { value: mirrored90 }
+}
+
+pub fn mirror_180(state: &SolitaireState) -> SolitaireState {
+    let mut mirrored180 = [[-1; 7]; 7];
+    for (row_idx, row) in state.value().iter().rev().enumerate() {
+        for (col_idx, val) in row.iter().enumerate() {
+            mirrored180[6 - row_idx][6 - col_idx] = *val
+        }
+    }
+    SolitaireState { value: mirrored180 }
+}
+
+pub fn mirror_270(state: &SolitaireState) -> SolitaireState {
+    let mut mirrored270 = [[-1; 7]; 7];
+    for (row_idx, row) in state.value().iter().rev().enumerate() {
+        for (col_idx, val) in row.iter().enumerate() {
+            mirrored270[col_idx][6 - row_idx] = *val
+        }
+    }
+    SolitaireState { value: mirrored270 }
+}
+
+pub fn identity(state: &SolitaireState) -> SolitaireState {
+    *state
+}
+
+/// all eight elements of the symmetry group of the board, starting with the identity
+pub const SYMMETRIES: [fn(&SolitaireState) -> SolitaireState; 8] =
+    [identity, rotate_90, rotate_180, rotate_270, mirror, mirror_90, mirror_180, mirror_270];
+
+// a row of the board takes STRIDE bits. The board is at most nine cells wide, so the last bit of
+// a row is always empty and shifting a peg one column to the left or right never wraps around into
+// another row
+pub const STRIDE: i32 = 10;
+
+/// Packed position of a board: bit `STRIDE * y + x` is set if there is a peg on `(x, y)`.
+/// Which bits belong to the board is described by its `BoardLayout`.
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Debug, PartialOrd, Ord)]
+pub struct Bitboard(pub u128);
+
+impl Bitboard {
+    pub fn index(p: Point) -> usize {
+        (p.y * STRIDE + p.x) as usize
+    }
+
+    pub fn bit(p: Point) -> u128 {
+        1 << Bitboard::index(p)
+    }
+
+    pub fn point(idx: u32) -> Point {
+        Point { x: idx as i32 % STRIDE, y: idx as i32 / STRIDE }
+    }
+
+    pub fn is_peg(&self, p: Point) -> bool {
+        p.x >= 0 && p.y >= 0 && p.x < STRIDE && Bitboard::index(p) < 128 && self.0 & Bitboard::bit(p) != 0
+    }
+
+    pub fn count_pegs(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// the pegs sorted by their coordinates
+    pub fn pegs(&self) -> Vec<Point> {
+        let mut bits = self.0;
+        let mut points = Vec::with_capacity(bits.count_ones() as usize);
+        while bits != 0 {
+            points.push(Bitboard::point(bits.trailing_zeros()));
+            bits &= bits - 1;
+        }
+        points.sort();
+        points
+    }
+}
+
+/// One of the 16 classes the positions of a square board fall into. Colour the cells by (x + y) mod 3:
+/// a jump along a row or a column changes the number of pegs on each of the three colours by one, so the
+/// parities of the sums of two colours stay the same. The same holds for the colouring by (x - y) mod 3,
+/// and a position can only reach positions of its own class as long as no peg jumps diagonally.
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Debug)]
+pub struct PositionClass(pub u8);
+
+impl PositionClass {
+    pub fn of(board: Bitboard) -> Self {
+        let mut counts = [0; 6];
+        for p in board.pegs() {
+            counts[(p.x + p.y).rem_euclid(3) as usize] += 1;
+            counts[3 + (p.x - p.y).rem_euclid(3) as usize] += 1;
+        }
+        let parities = [counts[0] + counts[1], counts[1] + counts[2], counts[3] + counts[4], counts[4] + counts[5]];
+        PositionClass(parities.iter().enumerate().fold(0, |class, (idx, count)| class | ((count % 2) << idx)))
+    }
+}
+
+/// Collision free key of a position which is shared by all positions that are symmetric to each other:
+/// the smallest bitboard encoding among them, see `BoardLayout::canonical_key`. Being an encoding
+/// itself, the position can be recovered with `board`.
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Debug, PartialOrd, Ord)]
+pub struct CanonicalKey(pub u128);
+
+impl CanonicalKey {
+    pub fn board(&self) -> Bitboard {
+        Bitboard(self.0)
+    }
+}
+
+impl Display for CanonicalKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<SolitaireState> for Bitboard {
+    fn from(state: SolitaireState) -> Self {
+        let mut bits = 0;
+        for (ridx, row) in state.value.iter().enumerate() {
+            for (cidx, value) in row.iter().enumerate() {
+                if *value == 1 {
+                    bits |= Bitboard::bit(Point { x: cidx as i32, y: ridx as i32 });
+                }
+            }
+        }
+        Bitboard(bits)
+    }
+}
+
+// SolitaireState only describes the English board
+impl From<Bitboard> for SolitaireState {
+    fn from(board: Bitboard) -> Self {
+        let mut value = get_empty_state();
+        for (ridx, row) in value.iter_mut().enumerate() {
+            for (cidx, value) in row.iter_mut().enumerate() {
+                if *value != -1 {
+                    *value = board.is_peg(Point { x: cidx as i32, y: ridx as i32 }) as i32;
+                }
+            }
+        }
+        SolitaireState { value }
+    }
 }
 
+#[derive(Clone)]
+pub struct Solitaire {
+    pub board: Bitboard,
+    pub layout: Arc<BoardLayout>,
+}
 
 pub fn get_empty_state() -> [[i32; 7]; 7] {
     let mut arr = [[0; 7]; 7];
@@ -107,77 +378,38 @@ pub fn get_start_state() -> [[i32; 7]; 7] {
     arr
 }
 
-impl Solitaire {
-    fn _set_point_to_value(&mut self, p: Point, value: i32) {
-        // only change value if it is inbound
-        if p.x > 0 || p.y > 0 || p.x < 6 || p.y < 6 {
-            self.state.value[p.y as usize][p.x as usize] = value
-        }
-    }
-
-    fn _get_value_of_point(&self, p: Point) -> i32 {
-        // return -1 if we are out of bounds
-        if p.x < 0 || p.y < 0 || p.x > 6 || p.y > 6 {
-            return -1;
-        }
-        self.state.value[p.y as usize][p.x as usize]
+impl Default for Solitaire {
+    fn default() -> Self {
+        Solitaire::new()
     }
+}
 
+impl Solitaire {
     pub fn finished(&self) -> bool {
-        match self.actions() {
-            None => true,
-            Some(_actions) => false,
-        }
+        self.layout.is_finished(self.board)
     }
 
     pub fn actions(&self) -> Option<Vec<SolitaireAction>> {
-        let mut possible_actions = Vec::new();
-        // todo: refactor. This works but is not really consice
-        for hole in &self.holes {
-            let right_jump = *hole + Point { x: -2, y: 0 };
-            let t = self._get_value_of_point(right_jump);
-            let pin = self._get_value_of_point(*hole + Point { x: -1, y: 0 });
-            // let t = self.state.value()[right_jump.y as usize][right_jump.x as usize];
-            if t == 1 && pin == 1 {
-                possible_actions.push(SolitaireAction {
-                    point: right_jump,
-                    action: Jump::Right,
-                });
-            };
+        let possible_actions = self.layout.actions(self.board);
+        match possible_actions.len() {
+            0 => None,
+            _ => Some(possible_actions),
+        }
+    }
 
-            let left_jump = *hole + Point { x: 2, y: 0 };
-            let t = self._get_value_of_point(left_jump);
-            let pin = self._get_value_of_point(*hole + Point { x: 1, y: 0 });
-            // let t = self.state.value()[left_jump.y as usize][left_jump.x as usize];
-            if t == 1 && pin == 1 {
-                possible_actions.push(SolitaireAction {
-                    point: left_jump,
-                    action: Jump::Left,
-                });
-            };
+    /// every move of a single peg jumping as often as it likes, see `BoardLayout::multi_jumps`
+    pub fn multi_jumps(&self) -> Vec<MultiJump> {
+        self.layout.multi_jumps(self.board).into_iter().map(|(multi_jump, _)| multi_jump).collect()
+    }
 
-            let down_jump = *hole + Point { x: 0, y: -2 };
-            let t = self._get_value_of_point(down_jump);
-            let pin = self._get_value_of_point(*hole + Point { x: 0, y: -1 });
-            // let t = self.state.value()[down_jump.y as usize][down_jump.x as usize];
-            if t == 1 && pin == 1 {
-                possible_actions.push(SolitaireAction {
-                    point: down_jump,
-                    action: Jump::Down,
-                });
-            };
+    /// play all jumps of the move, the reward is the number of pegs removed
+    pub fn take_multi_jump(&mut self, multi_jump: &MultiJump) -> f64 {
+        multi_jump.actions().iter().map(|action| self.take_action(&action.value())).sum()
+    }
 
-            let up_jump = *hole + Point { x: 0, y: 2 };
-            let t = self._get_value_of_point(up_jump);
-            // let t = self.state.value()[up_jump.y as usize][up_jump.x as usize];
-            let pin = self._get_value_of_point(*hole + Point { x: 0, y: 1 });
-            if t == 1 && pin == 1 {
-                possible_actions.push(SolitaireAction {
-                    point: up_jump,
-                    action: Jump::Up,
-                });
-            };
-        }
+    /// the jumps which could have led to the current position, see `BoardLayout::unjumps`
+    pub fn reverse_actions(&self) -> Option<Vec<SolitaireAction>> {
+        let possible_actions = self.layout.unjumps(self.board);
         match possible_actions.len() {
             0 => None,
             _ => Some(possible_actions),
@@ -185,134 +417,66 @@ impl Solitaire {
     }
 
     pub fn current_state(&self) -> StateT {
-        self.state.value()
+        self.state().value()
+    }
+
+    /// the position on the English grid, other boards are written with `BoardLayout::position_string`
+    pub fn state(&self) -> SolitaireState {
+        SolitaireState::from(self.board)
     }
 
+    pub fn holes(&self) -> Vec<Point> {
+        self.layout.holes(self.board)
+    }
+
+    pub fn pegs(&self) -> Vec<Point> {
+        self.board.pegs()
+    }
+
+    /// the central game on the English board, see `Problem` for other start positions
     pub fn new() -> Self {
-        let arr = get_start_state();
-        Solitaire {
-            state: SolitaireState { value: arr },
-            holes: vec![Point { x: 3, y: 3 }],
-            pegs: vec![
-                // 0-th row
-                Point { x: 2, y: 0},
-                Point { x: 3, y: 0},
-                Point { x: 4, y: 0},
-                // first row
-                Point { x: 2, y: 1},
-                Point { x: 3, y: 1},
-                Point { x: 4, y: 1},
-                // second row
-                Point { x: 0, y: 2},
-                Point { x: 1, y: 2},
-                Point { x: 2, y: 2},
-                Point { x: 3, y: 2},
-                Point { x: 4, y: 2},
-                Point { x: 5, y: 2},
-                Point { x: 6, y: 2},
-                // third row
-                Point { x: 0, y: 3},
-                Point { x: 1, y: 3},
-                Point { x: 2, y: 3},
-                // not this none
-                // Point { x: 3: y: 3},
-                Point { x: 4, y: 3},
-                Point { x: 5, y: 3},
-                Point { x: 6, y: 3},
-                // fourth row
-                Point { x: 0, y: 4},
-                Point { x: 1, y: 4},
-                Point { x: 2, y: 4},
-                Point { x: 3, y: 4},
-                Point { x: 4, y: 4},
-                Point { x: 5, y: 4},
-                Point { x: 6, y: 4},
-                // fifth row
-                Point { x: 2, y: 5},
-                Point { x: 3, y: 5},
-                Point { x: 4, y: 5},
-                // sixth row
-                Point { x: 2, y: 6},
-                Point { x: 3, y: 6},
-                Point { x: 4, y: 6},
-            ]
-        }
+        let layout = BoardLayout::english();
+        Solitaire { board: layout.start_position(), layout }
+    }
+
+    /// a game on another board, starting with a single hole at the default start hole of the geometry
+    pub fn with_geometry<G: BoardGeometry + ?Sized>(geometry: &G) -> Self {
+        Solitaire::with_rules(geometry, RuleSet::Standard)
+    }
+
+    /// like `with_geometry`, but the pegs move according to `rules`
+    pub fn with_rules<G: BoardGeometry + ?Sized>(geometry: &G, rules: RuleSet) -> Self {
+        let layout = Arc::new(BoardLayout::with_rules(geometry, rules));
+        Solitaire { board: layout.start_position(), layout }
+    }
+
+    pub fn from_layout(layout: Arc<BoardLayout>, board: Bitboard) -> Self {
+        Solitaire { board, layout }
     }
 
+    /// the given position on the board of this game
+    pub fn with_board(&self, board: Bitboard) -> Self {
+        Solitaire { board, layout: self.layout.clone() }
+    }
+
+    /// go back to the usual start position of the board
     pub fn reset(&mut self) {
-        let state = get_start_state();
-        self.state = SolitaireState { value: state };
-        self.holes = vec![Point { x: 3, y: 3 }];
-    }
-
-    pub fn simulate_action(&self, action: &ActionT) -> (SolitaireState, Vec<Point>, Vec<Point>) {
-        let state = self.state;
-        let mut holes = self.holes.clone();
-        let mut pegs = self.pegs.clone();
-
-        let (pin, jump) = action;
-        let (new_pin, removed_pin) = match *jump {
-            Jump::Down => (*pin + Point { x: 0, y: 2 }, *pin + Point { x: 0, y: 1 }),
-            Jump::Up => (*pin + Point { x: 0, y: -2 }, *pin + Point { x: 0, y: -1 }),
-            Jump::Left => (*pin + Point { x: -2, y: 0 }, *pin + Point { x: -1, y: 0 }),
-            Jump::Right => (*pin + Point { x: 2, y: 0 }, *pin + Point { x: 1, y: 0 }),
-        };
-        if self._get_value_of_point(new_pin) != 0 {
-            println!("This is self\n{}", self);
-            println!("This is the intended action: pin {:?} jump {:?}", pin, jump)
-        }
-        assert_eq!(self._get_value_of_point(new_pin), 0);
-        // IMPORTANT NOTE: self.state.value() is not easily modifiable, therefore we need self.state.value[...] here.
-        let mut state = self.state.clone();
-        state.value[new_pin.y as usize][new_pin.x as usize] = 1;
-        state.value[pin.y as usize][pin.x as usize] = 0;
-        state.value[removed_pin.y as usize][removed_pin.x as usize] = 0;
-
-        // todo: maybe refactor these iterators to single functions
-        let idx_hole = holes
-            .iter()
-            .enumerate()
-            .filter(|(_idx, &p)| p == new_pin)
-            .map(|(idx, _p)| idx)
-            .next()
-            .unwrap();
-        let idx_removed_pin = pegs
-            .iter()
-            .enumerate()
-            .filter(|(_idx, &p)| p == removed_pin)
-            .map(|(idx, _p)| idx)
-            .next()
-            .unwrap();
-        pegs.remove(idx_removed_pin);
-        let idx_jump_pin = pegs
-            .iter()
-            .enumerate()
-            .filter(|(_idx, &p)| p == *pin)
-            .map(|(idx, _p)| idx)
-            .next()
-            .unwrap();
-        pegs.remove(idx_jump_pin);
-        pegs.push(new_pin);
-
-        holes.remove(idx_hole);
-        holes.push(removed_pin);
-        holes.push(*pin);
-
-
-        // todo: do we need this?
-        holes.sort();
-        
-        (state, holes, pegs)
+        self.board = self.layout.start_position();
     }
 
-    pub fn take_action(&mut self, action: &ActionT) -> f64 {
-        let (state, holes, pegs) = self.simulate_action(action);
-        self.state = state;
-        self.holes = holes;
-        self.pegs = pegs;
+    pub fn simulate_action(&self, action: &ActionT) -> Bitboard {
+        let (pin, jump) = *action;
+        let removed_pin = pin + jump.offset();
+        let new_pin = removed_pin + jump.offset();
+        assert!(self.board.is_peg(pin), "no peg to jump with at {:?}\n{}", pin, self);
+        assert!(self.board.is_peg(removed_pin), "no peg to jump over at {:?}\n{}", removed_pin, self);
+        assert!(self.layout.is_hole(self.board, new_pin), "no hole to jump into at {:?}\n{}", new_pin, self);
+        Bitboard(self.board.0 ^ (Bitboard::bit(pin) | Bitboard::bit(removed_pin) | Bitboard::bit(new_pin)))
+    }
 
-        // for now we just add one to the reward for each removed pin, but
-        // we need to change this later on, since there needs to be a special reward if we end up in the middle
+    /// play the jump, the reward is the peg removed; the agents score jumps with a `RewardModel` instead
+    pub fn take_action(&mut self, action: &ActionT) -> f64 {
+        self.board = self.simulate_action(action);
         1.
     }
 
@@ -321,13 +485,12 @@ impl Solitaire {
         match actions {
             Some(actions) => {
                 let mut symmetry_reduced_actions: Vec<SolitaireAction> = Vec::new();
-                let mut seen_hashes: Vec<String> = Vec::new();
+                let mut seen_hashes: Vec<CanonicalKey> = Vec::new();
                 for action in actions {
-                    let (s, h, p) = self.simulate_action(&action.value());
-                    let hash = Solitaire::hash_state_as_string(&s, &h, &p);
-                    if !seen_hashes.contains(&&hash) {
-                        symmetry_reduced_actions.push(action.clone());
-                        seen_hashes.push(hash.clone());
+                    let hash = self.layout.canonical_key(self.simulate_action(&action.value()));
+                    if !seen_hashes.contains(&hash) {
+                        symmetry_reduced_actions.push(action);
+                        seen_hashes.push(hash);
                     }
                 }
                 Some(symmetry_reduced_actions)
@@ -336,7 +499,7 @@ impl Solitaire {
         }
     }
 
-    /// calculate the sum of distances between all points
+    // calculate the sum of distances between all points
     // pub fn hash(&self) -> (i8, f64, f64) {
     //     let length = self.holes.len();
     //     let mut sum : i32 = 0;
@@ -356,21 +519,28 @@ impl Solitaire {
     //     let a = 0.5 * sum as f64;
     //     (length as i8, a.abs(), min_distance)
     // }
-    
+
     // previous 20-11-22
     // pub fn hash(&self) -> (i8, f64, f64, f64, f64, i32) {
     pub fn hash(&self) -> (i8, f64, i32) {
-       Solitaire::hash_state(&self.state, &self.holes, &self.pegs)
+       Solitaire::hash_state(&self.state(), &self.holes(), &self.pegs())
+    }
+
+    pub fn canonical_key(&self) -> CanonicalKey {
+        self.layout.canonical_key(self.board)
+    }
+
+    pub fn position_class(&self) -> PositionClass {
+        PositionClass::of(self.board)
     }
 
     pub fn hash_as_str(&self) -> String {
-        Solitaire::hash_state_as_string(&self.state, &self.holes, &self.pegs)
+        Solitaire::hash_board_as_string(&self.board)
     }
 
-    pub fn calculate_distances(vec: &Vec<Point>) -> (i8, f64, f64) {
+    pub fn calculate_distances(vec: &[Point]) -> (i8, f64, f64) {
         let length = vec.len();
         let mut sum : f64 = 0.;
-        let vec = vec.clone();
         let mut sum_mid: f64 = 0.;
         for (idx, h) in vec[..length-1].iter().enumerate() {
             for h_next in vec[idx..].iter() {
@@ -382,11 +552,11 @@ impl Solitaire {
         // add last hole to sum_mid
         let lh = vec[length - 1];
         sum_mid += (( (lh.x - 3).pow(2) + (lh.y - 3).pow(2)) as f64).sqrt();
- 
+
         (length as i8, (sum * 1_000_000.).round() / 1_000_000., (sum_mid * 1_000_000.).round() / 1_000_000.)
     }
 
-    pub fn hash_state(state: &SolitaireState, holes: &Vec<Point>, pegs: &Vec<Point>) -> 
+    pub fn hash_state(state: &SolitaireState, holes: &[Point], _pegs: &[Point]) ->
         // previous 20-11-22
         //(i8, f64, f64, f64, f64, i32) {
         (i8, f64, i32) {
@@ -404,7 +574,7 @@ impl Solitaire {
         //// add last hole to sum_mid
         //let lh = holes[length - 1];
         //sum_mid += (( (lh.x - 3).pow(2) + (lh.y - 3).pow(2)) as f64).sqrt();
-        let (l, hd, hm) = Solitaire::calculate_distances(holes);
+        let (l, hd, _hm) = Solitaire::calculate_distances(holes);
 
         // previous 20-11-22
         // let (_, pd, _) = Solitaire::calculate_distances(pegs);
@@ -418,36 +588,42 @@ impl Solitaire {
         // }
 
         // previous 20-11-22
-        // (l, 
-        //  (hd * 1_000_000.).round() / 1_000_000., 
+        // (l,
+        //  (hd * 1_000_000.).round() / 1_000_000.,
         //  (hm * 1_000_000.).round() / 1_000_000.,
         //  (pd * 1_000_000.).round() / 1_000_000.,
         //  (hp * 1_000_000.).round() / 1_000_000.,
         //  Solitaire::hash_constant_groups(state))
-        (l, 
-         (hd * 1_000_000.).round() / 1_000_000., 
+        (l,
+         (hd * 1_000_000.).round() / 1_000_000.,
          Solitaire::hash_constant_groups(state))
     }
 
-    pub fn hash_state_as_string(state: &SolitaireState, holes: &Vec<Point>, pegs: &Vec<Point>) -> String {
+    pub fn hash_state_as_string(state: &SolitaireState, holes: &[Point], pegs: &[Point]) -> String {
         // see previous 20-11-22 in hash_state function!
         // let (num_holes, sum_of_dist, sum_of_dist_to_origin, pegs_distances, holes_peg_dist, const_group_hash) = Solitaire::hash_state(state, holes, pegs);
-        // let s = format!("{}_{}_{}_{}_{}_{}", num_holes, 
-        //                                      sum_of_dist, 
-        //                                      sum_of_dist_to_origin, 
-        //                                      pegs_distances, 
-        //                                      holes_peg_dist, 
+        // let s = format!("{}_{}_{}_{}_{}_{}", num_holes,
+        //                                      sum_of_dist,
+        //                                      sum_of_dist_to_origin,
+        //                                      pegs_distances,
+        //                                      holes_peg_dist,
         //                                      const_group_hash);
         let (num_holes, sum_of_dist, const_group_hash) = Solitaire::hash_state(state, holes, pegs);
-        let s = format!("{}_{}_{}", num_holes, 
-                                             sum_of_dist, 
-                                             // sum_of_dist_to_origin, 
-                                             // pegs_distances, 
-                                             // holes_peg_dist, 
+        let s = format!("{}_{}_{}", num_holes,
+                                             sum_of_dist,
+                                             // sum_of_dist_to_origin,
+                                             // pegs_distances,
+                                             // holes_peg_dist,
                                              const_group_hash);
         s
     }
 
+    // only defined for the English board
+    pub fn hash_board_as_string(board: &Bitboard) -> String {
+        let holes = BoardLayout::english().holes(*board);
+        Solitaire::hash_state_as_string(&SolitaireState::from(*board), &holes, &board.pegs())
+    }
+
     pub fn hash_constant_groups(state: &SolitaireState) -> i32 {
         let val = state.value();
         let mid = val[3][3];
@@ -457,65 +633,55 @@ impl Solitaire {
         let group5 = val[1][2] + val[1][4] + val[2][1] + val[2][5] + val [4][1] + val[4][5] + val[5][2] + val[5][4];
         let group6 = val[0][2] + val[0][4] + val[2][0] + val[2][6] + val [4][0] + val[4][6] + val[6][2] + val[6][4];
         let group7 = val[0][3] + val[3][0] + val[3][6] + val[6][3];
-        return 1_000_000 * mid + 100_000 * group2 + 10_000 * group3 + 1_000 * group4 + 100 * group5 + 10 * group6 + 1 * group7
+        1_000_000 * mid + 100_000 * group2 + 10_000 * group3 + 1_000 * group4 + 100 * group5 + 10 * group6 + group7
     }
 
     pub fn get_holes_from_state(state: &SolitaireState) -> Vec<Point> {
-        let mut holes = Vec::new();
-        for (ridx, row) in state.value.iter().enumerate() {
-            for (cidx, value) in row.iter().enumerate() {
-                if *value == 0 {
-                    holes.push(Point{ x: cidx as i32, y: ridx as i32});
-                }
-            }
-        }
-        holes
+        BoardLayout::english().holes(Bitboard::from(*state))
     }
 
     pub fn get_pegs_from_state(state: &SolitaireState) -> Vec<Point> {
-        let mut pegs = Vec::new();
-        for (ridx, row) in state.value.iter().enumerate() {
-            for (cidx, value) in row.iter().enumerate() {
-                if *value == 1 {
-                    pegs.push(Point{ x: cidx as i32, y: ridx as i32});
-                }
-            }
-        }
-        pegs
+        Bitboard::from(*state).pegs()
     }
 
     pub fn from_state(state: SolitaireState) -> Self {
-        let mut holes = Solitaire::get_holes_from_state(&state);
-        let pegs = Solitaire::get_pegs_from_state(&state);
-        holes.sort();
-        Solitaire {
-            state: state,
-            holes: holes,
-            pegs: pegs,
-        }
+        Solitaire::from_board(Bitboard::from(state))
+    }
+
+    /// a position on the English board
+    pub fn from_board(board: Bitboard) -> Self {
+        Solitaire { board, layout: BoardLayout::english() }
     }
 }
 
 impl Display for Solitaire {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        for row in self.state.value.iter() {
-            let v: String = row
-                .iter()
-                .map(|val| match val {
-                    1 => String::from(" x "),
-                    _ => String::from("   "),
+        for y in 0..self.layout.height {
+            let v: String = (0..self.layout.width)
+                .map(|x| match (self.board.is_peg(Point { x, y }), self.layout.triangular_grid) {
+                    (true, false) => String::from(" x "),
+                    (false, false) => String::from("   "),
+                    (true, true) => String::from("x "),
+                    (false, true) => String::from("  "),
                 })
                 .fold(String::new(), |a, b| a + &b);
-            writeln!(f, "{}", v).unwrap();
+            // on the triangular grid every row is shifted by half a cell to the left of the row above
+            match self.layout.triangular_grid {
+                true => writeln!(f, "{}{}", " ".repeat((self.layout.height - 1 - y) as usize), v).unwrap(),
+                false => writeln!(f, "{}", v).unwrap(),
+            }
         }
         Ok(())
     }
 }
 
 
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::board_geometry::{English, French, Hexagon, Triangle, Wiegleb};
     use rand::{Rng};
 
     #[test]
@@ -654,7 +820,7 @@ mod tests {
 
     #[test]
     fn test_get_symmetry_reduced_actions() {
-        let mut env = Solitaire::new();
+        let env = Solitaire::new();
         let result = env.get_symmetry_reduced_actions();
         let expected = Some(vec![SolitaireAction { point: Point { x: 1, y: 3 }, action: Jump::Right }]);
         assert_eq!(result, expected);
@@ -668,11 +834,11 @@ mod tests {
             action: Jump::Down,
             point: Point { x: 3, y: 1 },
         };
-        println!("These are the pegs before taking aciton {:?}\n\n", env.pegs);
+        println!("These are the pegs before taking aciton {:?}\n\n", env.pegs());
         println!("This is env\n{}", env);
         env.take_action(&action.value());
         println!("This is env\n{}", env);
-        assert_eq!(env.holes, vec![Point { x: 3, y: 1 }, Point { x: 3, y: 2 }]);
+        assert_eq!(env.holes(), vec![Point { x: 3, y: 1 }, Point { x: 3, y: 2 }]);
 
         let action = SolitaireAction {
             action: Jump::Right,
@@ -680,7 +846,7 @@ mod tests {
         };
         env.take_action(&action.value());
         assert_eq!(
-            env.holes,
+            env.holes(),
             vec![
                 Point { x: 1, y: 2 },
                 Point { x: 2, y: 2 },
@@ -688,7 +854,7 @@ mod tests {
             ]
         );
 
-        let mut resulting_pegs = env.pegs;
+        let mut resulting_pegs = env.pegs();
         resulting_pegs.sort();
         let mut expected_pegs = vec![
                 // 0-th row
@@ -781,8 +947,8 @@ mod tests {
             ],
         };
 
-        let mut env = Solitaire::from_state(state);
-        let (result, holes, pegs) = env.simulate_action(&(Point { x: 2, y: 1 }, Jump::Right));
+        let env = Solitaire::from_state(state);
+        let result = SolitaireState::from(env.simulate_action(&(Point { x: 2, y: 1 }, Jump::Right)));
 
         let expected = SolitaireState {
             value: [
@@ -797,86 +963,141 @@ mod tests {
         };
         assert_eq!(result, expected);
         // check that the state of env is unchanged
-        assert_eq!(env.state, state);
+        assert_eq!(env.state(), state);
     }
 
-    /// THIS WHOLE BLOG DOES TOUGH SHIT.
-    /// IT TRIES TO "PROOF" THAT OUR HASHING METHOD IS CORRECT
-    /// check if rotation works correctly
-    // helper functions to check rotation
-
-    pub fn _rotate_90(state: &SolitaireState) -> SolitaireState {
-        let mut s90 = [[-1; 7]; 7];
-        for (row_idx, row) in state.value().iter().rev().enumerate() {
-            for (col_idx, val) in row.iter().enumerate() {
-                s90[6 - col_idx][6 - row_idx] = *val
-            }
-        }
-        SolitaireState { value: s90 }
+    #[test]
+    #[should_panic(expected = "no peg to jump over")]
+    fn test_simulate_action_over_a_hole() {
+        let env = Solitaire::new();
+        env.simulate_action(&(Point { x: 3, y: 4 }, Jump::Up));
     }
 
-    pub fn _rotate_180(state: &SolitaireState) -> SolitaireState {
-        let mut s180 = [[-1; 7]; 7];
-        for (row_idx, row) in state.value().iter().rev().enumerate() {
-            for (col_idx, val) in row.iter().enumerate() {
-                s180[row_idx][6 - col_idx] = *val
-            }
-        }
-        SolitaireState { value: s180 }
+    #[test]
+    fn test_bitboard_round_trip() {
+        let state = SolitaireState { value: get_start_state() };
+        let board = Bitboard::from(state);
+        assert_eq!(board.count_pegs(), 32);
+        assert_eq!(BoardLayout::english().holes(board), vec![Point { x: 3, y: 3 }]);
+        assert_eq!(SolitaireState::from(board), state);
+
+        let empty = SolitaireState { value: get_empty_state() };
+        assert_eq!(Bitboard::from(empty), Bitboard(0));
+        assert_eq!(SolitaireState::from(Bitboard(0)), empty);
+        assert_eq!(Bitboard(BoardLayout::english().mask).count_pegs(), 33);
     }
 
-    pub fn _rotate_270(state: &SolitaireState) -> SolitaireState {
-        let mut s270 = [[-1; 7]; 7];
-        for (row_idx, row) in state.value().iter().rev().enumerate() {
-            for (col_idx, val) in row.iter().enumerate() {
-                s270[col_idx][row_idx] = *val
+    #[test]
+    fn test_canonical_key() {
+        let layout = BoardLayout::english();
+        let mut rng = rand::thread_rng();
+        for _ in 0..1_000 {
+            let board = Bitboard(rng.gen::<u128>() & layout.mask);
+            let key = layout.canonical_key(board);
+            let state = SolitaireState::from(board);
+            for (idx, symmetry) in SYMMETRIES.iter().enumerate() {
+                let transformed = Bitboard::from(symmetry(&state));
+                assert_eq!(layout.transform(board, idx), transformed);
+                assert_eq!(layout.canonical_key(transformed), key);
+                assert!(key.0 <= transformed.0);
             }
+            assert_eq!(layout.canonical_key(key.board()), key);
         }
-        SolitaireState { value: s270 }
+
+        // the position of test_hash_3_holes and a position with the same holes but at different places
+        let state = SolitaireState {
+            value: [
+                [-1, -1, 1, 1, 1, -1, -1],
+                [-1, -1, 1, 1, 1, -1, -1],
+                [ 1,  1, 1, 1, 1,  1,  1],
+                [ 1,  1, 1, 1, 1,  0,  1],
+                [ 1,  1, 1, 1, 0,  1,  1],
+                [-1, -1, 1, 1, 0, -1, -1],
+                [-1, -1, 1, 1, 1, -1, -1],
+            ],
+        };
+        let other = SolitaireState {
+            value: [
+                [-1, -1, 1, 1, 1, -1, -1],
+                [-1, -1, 1, 1, 1, -1, -1],
+                [ 1,  1, 1, 1, 1,  1,  1],
+                [ 1,  1, 1, 1, 1,  1,  0],
+                [ 1,  1, 1, 1, 0,  1,  1],
+                [-1, -1, 1, 1, 0, -1, -1],
+                [-1, -1, 1, 1, 1, -1, -1],
+            ],
+        };
+        assert_ne!(Solitaire::from_state(state).canonical_key(), Solitaire::from_state(other).canonical_key());
     }
 
-    pub fn _mirror(state: &SolitaireState) -> SolitaireState {
-        let mut mirrored = [[-1; 7]; 7];
-        for (row_idx, row) in state.value().iter().rev().enumerate() {
-            for (col_idx, val) in row.iter().enumerate() {
-                mirrored[row_idx][col_idx] = *val
-            }
-        }
-        SolitaireState { value: mirrored }
+    #[test]
+    fn test_position_string_round_trip() {
+        let state = SolitaireState { value: get_start_state() };
+        let position = state.to_string();
+        assert_eq!(position, "111111111111111101111111111111111");
+        let layout = BoardLayout::english();
+        assert_eq!(layout.position_string(Bitboard::from(state)), position);
+        assert_eq!(layout.board_from_position_string(&position), Some(Bitboard::from(state)));
+        assert_eq!(layout.board_from_position_string("1101"), None);
     }
 
-    pub fn _mirror_90(state: &SolitaireState) -> SolitaireState {
-        let mut mirrored90 = [[-1; 7]; 7];
-        for (row_idx, row) in state.value().iter().rev().enumerate() {
-            for (col_idx, val) in row.iter().enumerate() {
-                mirrored90[6 - col_idx][row_idx] = *val
+    #[test]
+    fn test_actions_match_cell_by_cell_search() {
+        let mut rng = rand::thread_rng();
+        let envs = [
+            Solitaire::new(),
+            Solitaire::with_geometry(&French),
+            Solitaire::with_geometry(&Wiegleb),
+            Solitaire::with_geometry(&Triangle::new(8)),
+            Solitaire::with_geometry(&Hexagon::new(5)),
+            Solitaire::with_rules(&English, RuleSet::Diagonal),
+            Solitaire::with_rules(&Wiegleb, RuleSet::Diagonal),
+        ];
+        for env in envs {
+            for _ in 0..1_000 {
+                assert_actions_match_cell_by_cell_search(&env.with_board(Bitboard(rng.gen::<u128>() & env.layout.mask)));
             }
         }
-        SolitaireState { value: mirrored90 }
     }
 
-    pub fn _mirror_180(state: &SolitaireState) -> SolitaireState {
-        let mut mirrored180 = [[-1; 7]; 7];
-        for (row_idx, row) in state.value().iter().rev().enumerate() {
-            for (col_idx, val) in row.iter().enumerate() {
-                mirrored180[6 - row_idx][6 - col_idx] = *val
+    fn assert_actions_match_cell_by_cell_search(env: &Solitaire) {
+        let board = env.board;
+        let mut expected = Vec::new();
+        for peg in board.pegs() {
+            for jump in env.layout.jumps.iter().copied() {
+                let over = peg + jump.offset();
+                if board.is_peg(over) && env.layout.is_hole(board, over + jump.offset()) {
+                    expected.push(SolitaireAction { point: peg, action: jump });
+                }
             }
         }
-        SolitaireState { value: mirrored180 }
-    }
-
-    pub fn _mirror_270(state: &SolitaireState) -> SolitaireState {
-        let mut mirrored270 = [[-1; 7]; 7];
-        for (row_idx, row) in state.value().iter().rev().enumerate() {
-            for (col_idx, val) in row.iter().enumerate() {
-                mirrored270[col_idx][6 - row_idx] = *val
+        let mut result = env.actions().unwrap_or_default();
+        let key = |a: &SolitaireAction| (a.point, a.action as usize);
+        result.sort_by_key(key);
+        expected.sort_by_key(key);
+        assert_eq!(result, expected);
+        assert_eq!(env.finished(), expected.is_empty());
+
+        // every unjump leads to a position from which the jump gets back here
+        let mut expected = Vec::new();
+        for peg in board.pegs() {
+            for jump in env.layout.jumps.iter().copied() {
+                let over = peg - jump.offset();
+                let from = over - jump.offset();
+                if env.layout.is_hole(board, over) && env.layout.is_hole(board, from) {
+                    expected.push(SolitaireAction { point: from, action: jump });
+                }
             }
         }
-        SolitaireState { value: mirrored270 }
-    }
-
-    pub fn generate_random_state() {
-
+        let mut result = env.reverse_actions().unwrap_or_default();
+        result.sort_by_key(key);
+        expected.sort_by_key(key);
+        assert_eq!(result, expected);
+        for action in result {
+            let previous = env.with_board(env.layout.unapply(board, action));
+            assert!(previous.actions().unwrap().contains(&action));
+            assert_eq!(previous.layout.apply(previous.board, action), board);
+        }
     }
 
 
@@ -893,16 +1114,16 @@ mod tests {
                 [-1, -1, 1, 1, 1, -1, -1],
             ],
         };
-        let mut env = Solitaire::from_state(state);
+        let env = Solitaire::from_state(state);
         // commented out on 29-11
         // let (num_holes, sum_of_dist, sum_of_dist_to_origin, pegs_dist, peg_hole_dist, const_group_hash) = env.hash();
-        let (num_holes, sum_of_dist, const_group_hash) = env.hash();
+        let (num_holes, _sum_of_dist, const_group_hash) = env.hash();
         assert_eq!(num_holes, 3);
         // assert_eq!(sum_of_dist, 11.162278);
         // assert_eq!(sum_of_dist_to_origin, 5.650282);
         assert_eq!(const_group_hash, 1_433_784);
 
-        let arr: Vec<fn(&SolitaireState) -> SolitaireState> = vec![_rotate_90, _rotate_180, _rotate_270, _mirror, _mirror_90, _mirror_180, _mirror_270];
+        let arr: Vec<fn(&SolitaireState) -> SolitaireState> = vec![rotate_90, rotate_180, rotate_270, mirror, mirror_90, mirror_180, mirror_270];
 
         for f in &arr {
             let env = Solitaire::from_state(f(&state));
@@ -932,11 +1153,11 @@ mod tests {
          ] 
         };
         let env = Solitaire::from_state(state);
-        println!("These are the pegs {:?}\n\n", env.pegs);
+        println!("These are the pegs {:?}\n\n", env.pegs());
         // commented out on 29-11
         // let (num_holes, area, min_dist, pegs_dist, peg_hole_dist, const_group_hash) = env.hash();
 
-        // let env = Solitaire::from_state(_rotate_90(&state));
+        // let env = Solitaire::from_state(rotate_90(&state));
         // println!("These are the pegs {:?}", env.pegs);
         // let (new_num_holes, new_area, new_min_dist, pegs_dist, new_peg_hole_dist, new_const_group_hash) = env.hash();
 
@@ -965,15 +1186,15 @@ mod tests {
         // commented out on 29-11
         //let (num_holes, area, min_dist, pegs_dist, peg_hole_dist, const_group_hash) = env.hash();
         let (num_holes, area, const_group_hash) = env.hash();
-        println!("These are the holes {:?}", env.holes);
+        println!("These are the holes {:?}", env.holes());
         println!("This is env\n{}\n\nAnd the num holes {}, area {}, const_group_hash {}", env, num_holes, area, const_group_hash);
 
-        let env = Solitaire::from_state(_rotate_90(&state));
-        println!("These are the holes {:?}", env.holes);
+        let env = Solitaire::from_state(rotate_90(&state));
+        println!("These are the holes {:?}", env.holes());
         // commented out on 29-11
         //let (new_num_holes, new_area, new_min_dist, pegs_dist, new_peg_hole_dist, new_const_group_hash) = env.hash();
         let (new_num_holes, new_area, new_const_group_hash) = env.hash();
-        println!("This is env2\n{}\n\nAnd the num holes {}, area {}, const_group_hash {}", env, new_num_holes, new_area, const_group_hash);
+        println!("This is env2\n{}\n\nAnd the num holes {}, area {}, const_group_hash {}", env, new_num_holes, new_area, new_const_group_hash);
 
         assert_eq!(num_holes, new_num_holes);
         assert_eq!(area, new_area);
@@ -996,7 +1217,7 @@ mod tests {
         };
 
         let env = Solitaire::from_state(state);
-        let result = Solitaire::hash_state_as_string(&env.state, &env.holes, &env.pegs);
+        let result = Solitaire::hash_state_as_string(&env.state(), &env.holes(), &env.pegs());
 
         // commented out on 29-11
         // assert_eq!(String::from("3_4.650282_5.650282_1370.759762_1433784"), result);
@@ -1019,7 +1240,7 @@ mod tests {
         };
 
         let env = Solitaire::from_state(state);
-        let result = Solitaire::hash_state_as_string(&env.state, &env.holes, &env.pegs);
+        let result = Solitaire::hash_state_as_string(&env.state(), &env.holes(), &env.pegs());
         // commented out on 29-11
         // assert_eq!(String::from("3_4.650282_5.650282_1370.759762_1433784"), result);
         assert_eq!(String::from("3_4.650282_1433784"), result);
@@ -1028,7 +1249,7 @@ mod tests {
 
     #[test]
     fn test_randomly_generated_state() {
-        let arr: Vec<fn(&SolitaireState) -> SolitaireState> = vec![_rotate_90, _rotate_180, _rotate_270, _mirror, _mirror_90, _mirror_180, _mirror_270];
+        let arr: Vec<fn(&SolitaireState) -> SolitaireState> = vec![rotate_90, rotate_180, rotate_270, mirror, mirror_90, mirror_180, mirror_270];
 
         for _ in 0..10_000 {
             let mut rng = rand::thread_rng();
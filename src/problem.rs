@@ -0,0 +1,269 @@
+use crate::board_geometry::BoardLayout;
+use crate::peg_solitaire_environment::{Bitboard, CanonicalKey, Jump, Point, PositionClass, Solitaire, SolitaireAction};
+use std::sync::Arc;
+
+/// When a game counts as solved.
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Debug)]
+pub enum Target {
+    /// a single peg is left on the given cell
+    SinglePegAt(Point),
+    /// exactly the given position is left
+    Board(Bitboard),
+    /// a single peg is left, no matter where
+    AnySinglePeg,
+}
+
+impl Target {
+    pub fn is_reached(&self, board: Bitboard) -> bool {
+        match self {
+            Target::SinglePegAt(p) => board.count_pegs() == 1 && board.is_peg(*p),
+            Target::Board(target) => board == *target,
+            Target::AnySinglePeg => board.count_pegs() == 1,
+        }
+    }
+
+    /// the number of pegs left when the target is reached
+    pub fn pegs(&self) -> u32 {
+        match self {
+            Target::Board(target) => target.count_pegs(),
+            _ => 1,
+        }
+    }
+}
+
+/// A puzzle: the position to start from, which also fixes board and rules, and the target to reach.
+#[derive(Clone)]
+pub struct Problem {
+    pub start: Solitaire,
+    pub target: Target,
+}
+
+impl Problem {
+    pub fn new(start: Solitaire, target: Target) -> Self {
+        Problem { start, target }
+    }
+
+    /// start with a single hole at `hole` and finish with a single peg at `survivor`
+    pub fn single_vacancy(layout: Arc<BoardLayout>, hole: Point, survivor: Point) -> Self {
+        assert!(layout.is_valid(hole) && layout.is_valid(survivor), "{:?} and {:?} have to be cells of the board", hole, survivor);
+        let board = layout.single_vacancy(hole);
+        Problem::new(Solitaire::from_layout(layout, board), Target::SinglePegAt(survivor))
+    }
+
+    /// the single vacancy problem which finishes with the last peg in the starting hole
+    pub fn complement(layout: Arc<BoardLayout>, hole: Point) -> Self {
+        Problem::single_vacancy(layout, hole, hole)
+    }
+
+    /// the classic problem on the English board: start with the center empty and finish with a peg in it
+    pub fn central_game() -> Self {
+        Problem::complement(BoardLayout::english(), Point { x: 3, y: 3 })
+    }
+
+    pub fn layout(&self) -> &Arc<BoardLayout> {
+        &self.start.layout
+    }
+
+    /// a fresh game at the start position
+    pub fn new_game(&self) -> Solitaire {
+        self.start.clone()
+    }
+
+    pub fn is_solved(&self, board: Bitboard) -> bool {
+        self.target.is_reached(board)
+    }
+
+    /// indices of the symmetries of the board which keep the target as it is. Only positions which are
+    /// mapped onto each other by these symmetries are equivalent for the problem.
+    pub fn symmetries(&self) -> Vec<usize> {
+        let layout = self.layout();
+        (0..layout.symmetries.len())
+            .filter(|idx| match self.target {
+                Target::SinglePegAt(p) => layout.symmetries[*idx].apply(p) == p,
+                Target::Board(target) => layout.transform(target, *idx) == target,
+                Target::AnySinglePeg => true,
+            })
+            .collect()
+    }
+
+    pub fn canonical_key(&self, board: Bitboard) -> CanonicalKey {
+        match self.target {
+            Target::AnySinglePeg => self.layout().canonical_key(board),
+            _ => self.layout().canonical_key_among(board, &self.symmetries()),
+        }
+    }
+
+    /// the actions of `env` without those which lead to a position equivalent to the one of an earlier action
+    pub fn symmetry_reduced_actions(&self, env: &Solitaire) -> Vec<SolitaireAction> {
+        let symmetries = self.symmetries();
+        let mut seen = Vec::new();
+        let mut actions = Vec::new();
+        for action in env.layout.actions(env.board) {
+            let key = env.layout.canonical_key_among(env.simulate_action(&action.value()), &symmetries);
+            if !seen.contains(&key) {
+                seen.push(key);
+                actions.push(action);
+            }
+        }
+        actions
+    }
+
+    /// the number of moves every solution takes, since each jump removes one peg
+    pub fn moves_to_solve(&self) -> Option<u32> {
+        self.start.board.count_pegs().checked_sub(self.target.pegs())
+    }
+
+    /// why the target can't be reached from the start, if this can be seen without searching
+    pub fn check_feasible(&self) -> Result<(), String> {
+        if self.moves_to_solve().is_none() {
+            return Err(format!(
+                "the start has {} pegs, but the target needs {}",
+                self.start.board.count_pegs(), self.target.pegs()
+            ));
+        }
+        let orthogonal = [Jump::Left, Jump::Down, Jump::Right, Jump::Up];
+        if !self.layout().jumps.iter().all(|jump| orthogonal.contains(jump)) {
+            return Ok(());
+        }
+        let class = self.start.position_class();
+        let target_classes: Vec<PositionClass> = match self.target {
+            Target::SinglePegAt(p) => vec![PositionClass::of(Bitboard(Bitboard::bit(p)))],
+            Target::Board(board) => vec![PositionClass::of(board)],
+            Target::AnySinglePeg => self.layout().cells.iter().map(|p| PositionClass::of(Bitboard(Bitboard::bit(*p)))).collect(),
+        };
+        match target_classes.contains(&class) {
+            true => Ok(()),
+            false => Err(format!(
+                "the start is in position class {} but the target in {:?}, and no jump changes the class",
+                class.0, target_classes.iter().map(|c| c.0).collect::<Vec<u8>>()
+            )),
+        }
+    }
+}
+
+/// whether the target is not ruled out by the number of pegs or the position classes, see `Problem::check_feasible`
+pub fn is_feasible(start: &Solitaire, target: &Target) -> bool {
+    Problem::new(start.clone(), *target).check_feasible().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::{English, French, RuleSet, Triangle};
+
+    #[test]
+    fn test_targets() {
+        let center = Point { x: 3, y: 3 };
+        let single = Bitboard(Bitboard::bit(center));
+        let other = Bitboard(Bitboard::bit(Point { x: 2, y: 3 }));
+
+        assert!(Target::SinglePegAt(center).is_reached(single));
+        assert!(!Target::SinglePegAt(center).is_reached(other));
+        assert!(!Target::SinglePegAt(center).is_reached(Bitboard(single.0 | other.0)));
+        assert!(Target::AnySinglePeg.is_reached(single));
+        assert!(Target::AnySinglePeg.is_reached(other));
+        assert!(!Target::AnySinglePeg.is_reached(Bitboard(0)));
+        assert!(Target::Board(Bitboard(single.0 | other.0)).is_reached(Bitboard(single.0 | other.0)));
+        assert!(!Target::Board(Bitboard(single.0 | other.0)).is_reached(single));
+        assert_eq!(Target::Board(Bitboard(single.0 | other.0)).pegs(), 2);
+    }
+
+    #[test]
+    fn test_central_game() {
+        let problem = Problem::central_game();
+        assert_eq!(problem.start.board, Solitaire::new().board);
+        assert_eq!(problem.target, Target::SinglePegAt(Point { x: 3, y: 3 }));
+        assert_eq!(problem.moves_to_solve(), Some(31));
+        assert!(!problem.is_solved(problem.start.board));
+        assert!(problem.is_solved(Bitboard(Bitboard::bit(Point { x: 3, y: 3 }))));
+    }
+
+    #[test]
+    fn test_problems_on_other_boards() {
+        let layout = Arc::new(BoardLayout::new(&French));
+        let problem = Problem::complement(layout.clone(), Point { x: 3, y: 1 });
+        assert_eq!(problem.start.board, layout.start_position());
+        assert_eq!(problem.layout().cells.len(), 37);
+        assert_eq!(problem.moves_to_solve(), Some(35));
+
+        let layout = Arc::new(BoardLayout::with_rules(&Triangle::CRACKER_BARREL, RuleSet::Standard));
+        let problem = Problem::single_vacancy(layout, Point { x: 0, y: 0 }, Point { x: 2, y: 4 });
+        assert_eq!(problem.new_game().layout.holes(problem.start.board), vec![Point { x: 0, y: 0 }]);
+        assert_eq!(problem.target, Target::SinglePegAt(Point { x: 2, y: 4 }));
+    }
+
+    #[test]
+    fn test_symmetries_of_the_target() {
+        assert_eq!(Problem::central_game().symmetries().len(), 8);
+        let layout = BoardLayout::english();
+        let start = Solitaire::from_layout(layout.clone(), layout.single_vacancy(Point { x: 3, y: 3 }));
+        assert_eq!(Problem::new(start.clone(), Target::AnySinglePeg).symmetries().len(), 8);
+
+        // a target on the vertical axis only keeps the identity and the reflection at that axis
+        let problem = Problem::single_vacancy(layout.clone(), Point { x: 3, y: 3 }, Point { x: 3, y: 0 });
+        assert_eq!(problem.symmetries().len(), 2);
+        // the first moves into the center are equivalent in pairs now
+        assert_eq!(problem.symmetry_reduced_actions(&problem.start).len(), 3);
+        assert_eq!(Problem::central_game().symmetry_reduced_actions(&problem.start).len(), 1);
+
+        let left = layout.single_vacancy(Point { x: 0, y: 3 });
+        let right = layout.single_vacancy(Point { x: 6, y: 3 });
+        let top = layout.single_vacancy(Point { x: 3, y: 0 });
+        assert_eq!(problem.canonical_key(left), problem.canonical_key(right));
+        assert_ne!(problem.canonical_key(left), problem.canonical_key(top));
+        assert_eq!(layout.canonical_key(left), layout.canonical_key(top));
+    }
+
+    #[test]
+    fn test_position_classes() {
+        let layout = BoardLayout::english();
+        // a single peg is in one of 9 classes, together with the positions of two pegs there are 16
+        let single = |p: &Point| Bitboard::bit(*p);
+        let mut classes: Vec<u8> = layout.cells.iter().map(|p| PositionClass::of(Bitboard(single(p))).0).collect();
+        classes.sort();
+        classes.dedup();
+        assert_eq!(classes.len(), 9);
+        for p in layout.cells.iter() {
+            for q in layout.cells.iter() {
+                classes.push(PositionClass::of(Bitboard(single(p) | single(q))).0);
+            }
+        }
+        classes.sort();
+        classes.dedup();
+        assert_eq!(classes, (0..16).collect::<Vec<u8>>());
+        // a jump never leaves its class
+        let env = Problem::central_game().start;
+        for action in layout.actions(env.board) {
+            assert_eq!(env.with_board(env.simulate_action(&action.value())).position_class(), env.position_class());
+        }
+    }
+
+    #[test]
+    fn test_feasible_problems_on_english_board() {
+        let layout = BoardLayout::english();
+        let feasible = |hole: Point| {
+            layout.cells.iter().filter(|s| Problem::single_vacancy(layout.clone(), hole, **s).check_feasible().is_ok()).count()
+        };
+        // the central game can only finish in the center or in the middle of one of the four outer edges
+        assert_eq!(feasible(Point { x: 3, y: 3 }), 5);
+        let problem = Problem::single_vacancy(layout.clone(), Point { x: 3, y: 3 }, Point { x: 3, y: 0 });
+        assert!(is_feasible(&problem.start, &problem.target));
+        let problem = Problem::single_vacancy(layout.clone(), Point { x: 3, y: 3 }, Point { x: 3, y: 2 });
+        assert!(problem.check_feasible().unwrap_err().contains("position class"));
+        // on this board every feasible problem is also solvable, so this is the number of solvable ones
+        assert_eq!(layout.cells.iter().map(|hole| feasible(*hole)).sum::<usize>(), 125);
+
+        let start = Problem::central_game().start;
+        assert!(is_feasible(&start, &Target::AnySinglePeg));
+        assert!(!is_feasible(&start.with_board(Bitboard(0)), &Target::AnySinglePeg));
+        // diagonal jumps mix the classes
+        let diagonal = Solitaire::with_rules(&English, RuleSet::Diagonal);
+        assert!(is_feasible(&diagonal, &Target::SinglePegAt(Point { x: 3, y: 2 })));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_single_vacancy_outside_board() {
+        Problem::complement(BoardLayout::english(), Point { x: 0, y: 0 });
+    }
+}
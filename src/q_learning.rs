@@ -0,0 +1,331 @@
+use crate::board_geometry::BoardLayout;
+use crate::peg_solitaire_environment::{Bitboard, SolitaireAction};
+use crate::problem::Problem;
+use crate::reward::{RewardModel, DEFAULT_REWARDS};
+use crate::solver::PositionHasher;
+use crate::state_function::SOLVED_BONUS;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::hash::BuildHasherDefault;
+use std::sync::Arc;
+
+/// How the value of the next position enters the update of an action value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// the best action of the next position, off policy
+    QLearning,
+    /// the action which is actually played next
+    Sarsa,
+    /// the average over the next actions weighted with the epsilon greedy probabilities
+    ExpectedSarsa,
+}
+
+/// The probability of playing a random action instead of the best one, by episode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EpsilonSchedule {
+    Constant(f64),
+    /// from `start` down to `end` over the first `episodes` episodes
+    Linear { start: f64, end: f64, episodes: u64 },
+    /// `start` multiplied by `decay` every episode, but never below `end`
+    Exponential { start: f64, end: f64, decay: f64 },
+}
+
+impl EpsilonSchedule {
+    pub fn epsilon(&self, episode: u64) -> f64 {
+        match *self {
+            EpsilonSchedule::Constant(epsilon) => epsilon,
+            EpsilonSchedule::Linear { start, end, episodes } => match episode >= episodes {
+                true => end,
+                false => start + (end - start) * episode as f64 / episodes as f64,
+            },
+            EpsilonSchedule::Exponential { start, end, decay } => (start * decay.powf(episode as f64)).max(end),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QConfig {
+    /// learning rate
+    pub alpha: f64,
+    /// discount of the value of the next position
+    pub gamma: f64,
+    pub epsilon: EpsilonSchedule,
+    pub episodes: u64,
+    /// episodes which are summarized in one point of the learning curve
+    pub window: u64,
+    pub seed: u64,
+    /// the value of actions which were never updated, a value above what most actions are worth makes the
+    /// agent try them, otherwise it rarely finds the target
+    pub initial_value: f64,
+}
+
+impl Default for QConfig {
+    fn default() -> Self {
+        QConfig {
+            alpha: 0.1,
+            gamma: 1.,
+            epsilon: EpsilonSchedule::Linear { start: 1., end: 0.05, episodes: 10_000 },
+            episodes: 20_000,
+            window: 1000,
+            seed: 0,
+            initial_value: SOLVED_BONUS,
+        }
+    }
+}
+
+/// Action values of the positions of one board. Symmetric pairs of position and action share their value:
+/// the key is the compressed canonical position together with the action moved along with the position.
+pub struct QTable {
+    pub values: HashMap<(u64, SolitaireAction), f64, BuildHasherDefault<PositionHasher>>,
+    layout: Arc<BoardLayout>,
+    symmetries: Vec<usize>,
+    /// the value of actions without an entry
+    pub initial_value: f64,
+}
+
+impl QTable {
+    pub fn new(problem: &Problem) -> Self {
+        QTable { values: HashMap::default(), layout: problem.layout().clone(), symmetries: problem.symmetries(), initial_value: 0. }
+    }
+
+    // the canonical position and every symmetry which maps the position onto it
+    fn canonical(&self, board: Bitboard) -> (u64, Vec<usize>) {
+        let images: Vec<(Bitboard, usize)> = self.symmetries.iter().map(|idx| (self.layout.transform(board, *idx), *idx)).collect();
+        let canonical = images.iter().map(|(image, _)| *image).min().unwrap();
+        let symmetries = images.into_iter().filter(|(image, _)| *image == canonical).map(|(_, idx)| idx).collect();
+        (self.layout.compress(canonical), symmetries)
+    }
+
+    // if the canonical position is symmetric itself, the action is the smallest of its images
+    fn key(&self, (canonical, symmetries): &(u64, Vec<usize>), action: SolitaireAction) -> (u64, SolitaireAction) {
+        let image = symmetries
+            .iter()
+            .map(|idx| self.layout.transform_action(action, *idx))
+            .min_by_key(|a| (a.point.y, a.point.x, a.action as usize))
+            .unwrap();
+        (*canonical, image)
+    }
+
+    pub fn value(&self, board: Bitboard, action: SolitaireAction) -> f64 {
+        self.values(board, &[action])[0]
+    }
+
+    /// the values of the actions, in the same order
+    pub fn values(&self, board: Bitboard, actions: &[SolitaireAction]) -> Vec<f64> {
+        let canonical = self.canonical(board);
+        actions.iter().map(|a| self.values.get(&self.key(&canonical, *a)).copied().unwrap_or(self.initial_value)).collect()
+    }
+
+    pub fn set_value(&mut self, board: Bitboard, action: SolitaireAction, value: f64) {
+        let key = self.key(&self.canonical(board), action);
+        self.values.insert(key, value);
+    }
+
+    /// the action with the highest value, the first one on ties
+    pub fn greedy_action(&self, board: Bitboard) -> Option<SolitaireAction> {
+        let actions = self.layout.actions(board);
+        let values = self.values(board, &actions);
+        (0..actions.len()).max_by(|a, b| values[*a].total_cmp(&values[*b]).then(b.cmp(a))).map(|idx| actions[idx])
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// The results of the episodes of one window of training.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CurvePoint {
+    /// episodes played up to the end of the window
+    pub episodes: u64,
+    /// share of the episodes of the window which reached the target
+    pub success_rate: f64,
+    /// average number of pegs left at the end of the episodes of the window
+    pub mean_pegs: f64,
+    /// epsilon of the last episode of the window
+    pub epsilon: f64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LearningCurve {
+    pub points: Vec<CurvePoint>,
+}
+
+impl LearningCurve {
+    pub fn report(&self) -> String {
+        let mut s = String::new();
+        writeln!(&mut s, "episodes  success rate  pegs left  epsilon").unwrap();
+        for p in self.points.iter() {
+            writeln!(&mut s, "{:>8} {:>13.3} {:>10.2} {:>8.3}", p.episodes, p.success_rate, p.mean_pegs, p.epsilon).unwrap();
+        }
+        s
+    }
+}
+
+/// A tabular agent which learns the action values of a problem from epsilon greedy episodes.
+pub struct QAgent<'a> {
+    pub problem: &'a Problem,
+    pub table: QTable,
+    pub config: QConfig,
+    pub algorithm: Algorithm,
+    /// how the jumps are rewarded, `DEFAULT_REWARDS` unless set
+    pub rewards: &'a dyn RewardModel,
+    rng: StdRng,
+}
+
+impl<'a> QAgent<'a> {
+    pub fn new(problem: &'a Problem, algorithm: Algorithm, config: QConfig) -> Self {
+        let table = QTable { initial_value: config.initial_value, ..QTable::new(problem) };
+        QAgent { problem, table, config, algorithm, rewards: &DEFAULT_REWARDS, rng: StdRng::seed_from_u64(config.seed) }
+    }
+
+    fn epsilon_greedy(&mut self, board: Bitboard, actions: &[SolitaireAction], epsilon: f64) -> SolitaireAction {
+        match self.rng.gen::<f64>() < epsilon {
+            true => *actions.choose(&mut self.rng).unwrap(),
+            false => self.table.greedy_action(board).unwrap(),
+        }
+    }
+
+    // the value of the next position which the update moves towards
+    fn next_value(&self, board: Bitboard, actions: &[SolitaireAction], next: SolitaireAction, epsilon: f64) -> f64 {
+        let values = self.table.values(board, actions);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        match self.algorithm {
+            Algorithm::QLearning => max,
+            Algorithm::Sarsa => self.table.value(board, next),
+            Algorithm::ExpectedSarsa => {
+                let mean = values.iter().sum::<f64>() / values.len() as f64;
+                (1. - epsilon) * max + epsilon * mean
+            }
+        }
+    }
+
+    /// Play one game with the epsilon of the episode and update the value of every action played.
+    /// Returns the pegs left and whether the target was reached.
+    pub fn run_episode(&mut self, episode: u64) -> (u32, bool) {
+        let layout = self.problem.layout().clone();
+        let epsilon = self.config.epsilon.epsilon(episode);
+        let mut board = self.problem.start.board;
+        let actions = layout.actions(board);
+        let mut action = match actions.is_empty() {
+            true => None,
+            false => Some(self.epsilon_greedy(board, &actions, epsilon)),
+        };
+        while let Some(a) = action {
+            let next_board = layout.apply(board, a);
+            let reward = self.rewards.reward(self.problem, board, next_board);
+            let next_actions = layout.actions(next_board);
+            let (target, next_action) = match next_actions.is_empty() {
+                true => (reward, None),
+                false => {
+                    let next = self.epsilon_greedy(next_board, &next_actions, epsilon);
+                    (reward + self.config.gamma * self.next_value(next_board, &next_actions, next, epsilon), Some(next))
+                }
+            };
+            let value = self.table.value(board, a);
+            self.table.set_value(board, a, value + self.config.alpha * (target - value));
+            board = next_board;
+            action = next_action;
+        }
+        (board.count_pegs(), self.problem.is_solved(board))
+    }
+
+    /// Run `config.episodes` episodes and summarize every `config.window` of them.
+    pub fn train(&mut self) -> LearningCurve {
+        let mut curve = LearningCurve::default();
+        let (mut solved, mut pegs, mut played) = (0, 0, 0);
+        for episode in 0..self.config.episodes {
+            let (left, success) = self.run_episode(episode);
+            solved += success as u64;
+            pegs += left as u64;
+            played += 1;
+            if played == self.config.window || episode + 1 == self.config.episodes {
+                curve.points.push(CurvePoint {
+                    episodes: episode + 1,
+                    success_rate: solved as f64 / played as f64,
+                    mean_pegs: pegs as f64 / played as f64,
+                    epsilon: self.config.epsilon.epsilon(episode),
+                });
+                (solved, pegs, played) = (0, 0, 0);
+            }
+        }
+        curve
+    }
+
+    /// the game of always playing the action with the highest value
+    pub fn greedy_game(&self) -> Vec<SolitaireAction> {
+        let mut board = self.problem.start.board;
+        let mut game = Vec::new();
+        while let Some(action) = self.table.greedy_action(board) {
+            game.push(action);
+            board = self.problem.layout().apply(board, action);
+        }
+        game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_problems::triangle;
+
+    fn solves(problem: &Problem, game: &[SolitaireAction]) -> bool {
+        let board = game.iter().fold(problem.start.board, |board, action| problem.layout().apply(board, *action));
+        problem.is_solved(board)
+    }
+
+    #[test]
+    fn test_epsilon_schedules() {
+        let linear = EpsilonSchedule::Linear { start: 1., end: 0.1, episodes: 10 };
+        assert_eq!(linear.epsilon(0), 1.);
+        assert!((linear.epsilon(5) - 0.55).abs() < 1e-12);
+        assert_eq!(linear.epsilon(100), 0.1);
+        let exponential = EpsilonSchedule::Exponential { start: 1., end: 0.1, decay: 0.5 };
+        assert_eq!(exponential.epsilon(1), 0.5);
+        assert_eq!(exponential.epsilon(10), 0.1);
+        assert_eq!(EpsilonSchedule::Constant(0.3).epsilon(7), 0.3);
+    }
+
+    #[test]
+    fn test_symmetric_actions_share_values() {
+        let problem = Problem::central_game();
+        let layout = problem.layout();
+        let mut table = QTable::new(&problem);
+        let action = layout.actions(problem.start.board)[0];
+        table.set_value(problem.start.board, action, 2.);
+        // every first jump of the central game is the image of any other one
+        for other in layout.actions(problem.start.board) {
+            assert_eq!(table.value(problem.start.board, other), 2.);
+        }
+        assert_eq!(table.len(), 1);
+        let board = layout.apply(problem.start.board, action);
+        for idx in problem.symmetries() {
+            let image = layout.transform_action(layout.actions(board)[0], idx);
+            table.set_value(layout.transform(board, idx), image, 1.);
+        }
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn test_agents_learn_the_triangle() {
+        let problem = triangle();
+        for algorithm in [Algorithm::QLearning, Algorithm::Sarsa, Algorithm::ExpectedSarsa] {
+            let config = QConfig { alpha: 0.2, episodes: 5000, window: 500, ..Default::default() };
+            let config = QConfig { epsilon: EpsilonSchedule::Linear { start: 1., end: 0.05, episodes: 4000 }, ..config };
+            let mut agent = QAgent::new(&problem, algorithm, config);
+            let curve = agent.train();
+            assert_eq!(curve.points.len(), 10);
+            let (first, last) = (curve.points[0], *curve.points.last().unwrap());
+            assert!(last.success_rate > first.success_rate, "{:?}\n{}", algorithm, curve.report());
+            assert!(last.mean_pegs < first.mean_pegs, "{:?}", algorithm);
+            assert!(solves(&problem, &agent.greedy_game()), "{:?}", algorithm);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::board_geometry::BoardLayout;
+use crate::peg_solitaire_environment::{Bitboard, SolitaireAction};
+use crate::problem::{Problem, Target};
+use crate::solver::PositionSet;
+use crate::reward::RewardModel;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Every position with up to `max_pegs` pegs from which the target of a problem can be reached, found by
+/// playing unjumps backwards from the target one peg layer after the other.
+#[derive(Debug)]
+pub struct Tablebase {
+    pub layout: Arc<BoardLayout>,
+    pub target: Target,
+    /// the symmetries of the board which keep the target, see `Problem::symmetries`
+    symmetries: Vec<usize>,
+    /// `layers[n]` holds the compressed canonical keys of the positions with `n` pegs
+    pub layers: Vec<PositionSet>,
+}
+
+impl Tablebase {
+    /// Only the board, the rules and the target of the problem matter, not its start position.
+    pub fn build(problem: &Problem, max_pegs: u32) -> Self {
+        let layout = problem.layout().clone();
+        let mut tablebase = Tablebase {
+            layout: layout.clone(),
+            target: problem.target,
+            symmetries: problem.symmetries(),
+            layers: vec![PositionSet::default(); max_pegs as usize + 1],
+        };
+        let targets: Vec<Bitboard> = match problem.target {
+            Target::SinglePegAt(p) => vec![Bitboard(Bitboard::bit(p))],
+            Target::Board(board) => vec![board],
+            Target::AnySinglePeg => layout.cells.iter().map(|p| Bitboard(Bitboard::bit(*p))).collect(),
+        };
+        let pegs = problem.target.pegs() as usize;
+        if pegs > max_pegs as usize {
+            return tablebase;
+        }
+        let mut frontier = Vec::new();
+        for board in targets {
+            let key = tablebase.key(board);
+            if tablebase.layers[pegs].insert(key) {
+                frontier.push(board);
+            }
+        }
+        for n in pegs + 1..=max_pegs as usize {
+            let mut next = Vec::new();
+            for board in frontier {
+                for jump in layout.jumps.iter() {
+                    let mut holes = layout.unjumpable_holes(board, *jump);
+                    while holes != 0 {
+                        let action = SolitaireAction { point: Bitboard::point(holes.trailing_zeros()), action: *jump };
+                        let previous = layout.unapply(board, action);
+                        let key = tablebase.key(previous);
+                        if tablebase.layers[n].insert(key) {
+                            next.push(previous);
+                        }
+                        holes &= holes - 1;
+                    }
+                }
+            }
+            frontier = next;
+        }
+        tablebase
+    }
+
+    fn key(&self, board: Bitboard) -> u64 {
+        self.layout.compress(self.layout.canonical_key_among(board, &self.symmetries).board())
+    }
+
+    pub fn max_pegs(&self) -> u32 {
+        self.layers.len() as u32 - 1
+    }
+
+    /// whether the target can be reached from the position, `None` if it has more pegs than the table covers
+    pub fn can_reach_target(&self, board: Bitboard) -> Option<bool> {
+        self.layers.get(board.count_pegs() as usize).map(|layer| layer.contains(&self.key(board)))
+    }
+
+    /// the jumps from the position to the target, if it is in the table
+    pub fn solution(&self, board: Bitboard) -> Option<Vec<SolitaireAction>> {
+        if self.can_reach_target(board) != Some(true) {
+            return None;
+        }
+        let mut board = board;
+        let mut solution = Vec::new();
+        while !self.target.is_reached(board) {
+            let action = self
+                .layout
+                .actions(board)
+                .into_iter()
+                .find(|action| self.can_reach_target(self.layout.apply(board, *action)) == Some(true))
+                .expect("every position of the table except the target has a jump into the next layer");
+            board = self.layout.apply(board, action);
+            solution.push(action);
+        }
+        Some(solution)
+    }
+
+    /// The value `StateFunction` would learn for a position reached with `reward` so far if it searched the
+    /// rest of the game: the target is reached, which the rewards may not be able to tell without playing.
+    pub fn value(&self, problem: &Problem, rewards: &dyn RewardModel, board: Bitboard, reward: f64) -> Option<f64> {
+        match self.can_reach_target(board) {
+            Some(true) => rewards.solved_return(problem, board).map(|rest| reward + rest),
+            _ => None,
+        }
+    }
+
+    /// the number of positions in every layer, up to symmetry
+    pub fn layer_sizes(&self) -> Vec<usize> {
+        self.layers.iter().map(|layer| layer.len()).collect()
+    }
+
+    pub fn report(&self) -> String {
+        let mut s = String::new();
+        writeln!(&mut s, "Positions on the {} board which can reach {:?}, up to symmetry", self.layout.name, self.target).unwrap();
+        for (pegs, size) in self.layer_sizes().iter().enumerate().filter(|(_, size)| **size > 0) {
+            writeln!(&mut s, "{:2} pegs: {}", pegs, size).unwrap();
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::Triangle;
+    use crate::peg_solitaire_environment::{Point, Solitaire};
+    use crate::reward::{IsolatedPegPenalty, TerminalSuccess, DEFAULT_REWARDS};
+    use crate::solver::solve;
+    use crate::state_function::SOLVED_BONUS;
+    use crate::test_problems::english_endgame;
+    use rand::Rng;
+
+    #[test]
+    fn test_tablebase_central_game() {
+        let problem = Problem::central_game();
+        let tablebase = Tablebase::build(&problem, 6);
+        // the last jump into the center comes from one of the four sides, which are all symmetric
+        assert_eq!(&tablebase.layer_sizes()[..3], &[0, 1, 1]);
+        assert_eq!(tablebase.can_reach_target(problem.start.board), None);
+
+        // the position five moves before the end of a solution of the central game
+        let layout = problem.layout();
+        let middlegame = english_endgame().start.board;
+        let moves = solve(&Problem::new(problem.start.with_board(middlegame), problem.target)).unwrap();
+        let board = moves[..moves.len() - 4].iter().fold(middlegame, |board, action| layout.apply(board, *action));
+        assert_eq!(board.count_pegs(), 5);
+        assert_eq!(tablebase.can_reach_target(board), Some(true));
+        let solution = tablebase.solution(board).unwrap();
+        assert_eq!(solution.len(), 4);
+        let end = solution.iter().fold(board, |board, action| layout.apply(board, *action));
+        assert!(problem.is_solved(end));
+        assert_eq!(tablebase.value(&problem, &DEFAULT_REWARDS, board, 27.), Some(31. + SOLVED_BONUS));
+        assert_eq!(tablebase.value(&problem, &TerminalSuccess, board, 0.), Some(1.));
+        let penalty = IsolatedPegPenalty { base: Box::new(DEFAULT_REWARDS), penalty: 1. };
+        assert_eq!(tablebase.value(&problem, &penalty, board, 27.), None);
+    }
+
+    #[test]
+    fn test_tablebase_agrees_with_solver() {
+        let problem = Problem::central_game();
+        let tablebase = Tablebase::build(&problem, 7);
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let mut env = problem.new_game();
+            while env.board.count_pegs() > 7 && !env.finished() {
+                let actions = env.actions().unwrap();
+                env.take_action(&actions[rng.gen_range(0..actions.len())].value());
+            }
+            let endgame = Problem::new(env.clone(), problem.target);
+            assert_eq!(tablebase.can_reach_target(env.board).unwrap_or(false), solve(&endgame).is_some());
+        }
+    }
+
+    #[test]
+    fn test_tablebase_any_single_peg() {
+        let env = Solitaire::with_geometry(&Triangle::CRACKER_BARREL);
+        let tablebase = Tablebase::build(&Problem::new(env.clone(), Target::AnySinglePeg), 14);
+        // the 15 hole triangle can be solved from its corner
+        assert_eq!(tablebase.can_reach_target(env.board), Some(true));
+        assert_eq!(tablebase.solution(env.board).unwrap().len(), 13);
+        assert_eq!(tablebase.can_reach_target(Bitboard(Bitboard::bit(Point { x: 2, y: 4 }))), Some(true));
+    }
+}
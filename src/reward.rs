@@ -0,0 +1,191 @@
+use crate::heuristic::{Heuristic, IsolatedPegs};
+use crate::pagoda::{Pagoda, PagodaPruning};
+use crate::peg_solitaire_environment::Bitboard;
+use crate::problem::Problem;
+use crate::state_function::SOLVED_BONUS;
+
+/// How the jumps of a game are rewarded. The learners maximize the sum of the rewards of a game and the searches
+/// compare games by it, so the same agent can be run against different rewards.
+pub trait RewardModel {
+    fn name(&self) -> String;
+    /// the reward of the jump from `board` to `next`, including the reward for the end of the game if `next`
+    /// is finished
+    fn reward(&self, problem: &Problem, board: Bitboard, next: Bitboard) -> f64;
+    /// the sum of the rewards of the rest of a game which reaches the target from `board`, if it is the same for
+    /// every way to get there
+    fn solved_return(&self, _problem: &Problem, _board: Bitboard) -> Option<f64> {
+        None
+    }
+}
+
+/// one for every jump, that is every peg removed
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PegsRemoved;
+
+impl RewardModel for PegsRemoved {
+    fn name(&self) -> String {
+        String::from("pegs removed")
+    }
+
+    fn reward(&self, _problem: &Problem, _board: Bitboard, _next: Bitboard) -> f64 {
+        1.
+    }
+
+    fn solved_return(&self, problem: &Problem, board: Bitboard) -> Option<f64> {
+        Some((board.count_pegs() - problem.target.pegs()) as f64)
+    }
+}
+
+/// one for a game which ends on the target and nothing else
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TerminalSuccess;
+
+impl RewardModel for TerminalSuccess {
+    fn name(&self) -> String {
+        String::from("terminal success")
+    }
+
+    fn reward(&self, problem: &Problem, _board: Bitboard, next: Bitboard) -> f64 {
+        (problem.layout().is_finished(next) && problem.is_solved(next)) as u8 as f64
+    }
+
+    fn solved_return(&self, problem: &Problem, board: Bitboard) -> Option<f64> {
+        Some(!problem.layout().is_finished(board) as u8 as f64)
+    }
+}
+
+/// One for every jump and `bonus` on top for a game which ends on the target, which is the center in the
+/// central game. With `SOLVED_BONUS` this is what all agents use unless they are given another model.
+#[derive(Clone, Copy, Debug)]
+pub struct CenterBonus {
+    pub bonus: f64,
+}
+
+impl Default for CenterBonus {
+    fn default() -> Self {
+        DEFAULT_REWARDS
+    }
+}
+
+/// the rewards of the agents unless they are given other ones
+pub const DEFAULT_REWARDS: CenterBonus = CenterBonus { bonus: SOLVED_BONUS };
+
+impl RewardModel for CenterBonus {
+    fn name(&self) -> String {
+        format!("center bonus of {}", self.bonus)
+    }
+
+    fn reward(&self, problem: &Problem, _board: Bitboard, next: Bitboard) -> f64 {
+        match problem.layout().is_finished(next) && problem.is_solved(next) {
+            true => 1. + self.bonus,
+            false => 1.,
+        }
+    }
+
+    fn solved_return(&self, problem: &Problem, board: Bitboard) -> Option<f64> {
+        let bonus = if problem.layout().is_finished(board) { 0. } else { self.bonus };
+        Some((board.count_pegs() - problem.target.pegs()) as f64 + bonus)
+    }
+}
+
+/// Potential based shaping of another model: every jump also gets the change of the potential, `weight` times
+/// how far the pagodas of the problem are above their value at the target. Finished positions have no potential,
+/// so the shaping of a whole game only depends on its start and the best games stay the same.
+pub struct PagodaShaping {
+    pub base: Box<dyn RewardModel>,
+    /// the pagodas of `PagodaPruning::for_problem` with their value at the target
+    pub pagodas: Vec<(Pagoda, i32)>,
+    pub weight: f64,
+}
+
+impl PagodaShaping {
+    pub fn new(problem: &Problem, base: Box<dyn RewardModel>, weight: f64) -> Self {
+        let pruning = PagodaPruning::for_problem(problem);
+        PagodaShaping { base, pagodas: pruning.pagodas.into_iter().zip(pruning.target_values).collect(), weight }
+    }
+
+    pub fn potential(&self, problem: &Problem, board: Bitboard) -> f64 {
+        match problem.layout().is_finished(board) {
+            true => 0.,
+            false => self.weight * self.pagodas.iter().map(|(pagoda, target)| (pagoda.value(board) - target) as f64).sum::<f64>(),
+        }
+    }
+}
+
+impl RewardModel for PagodaShaping {
+    fn name(&self) -> String {
+        format!("{} shaped by {} pagodas", self.base.name(), self.pagodas.len())
+    }
+
+    fn reward(&self, problem: &Problem, board: Bitboard, next: Bitboard) -> f64 {
+        self.base.reward(problem, board, next) + self.potential(problem, next) - self.potential(problem, board)
+    }
+
+    fn solved_return(&self, problem: &Problem, board: Bitboard) -> Option<f64> {
+        self.base.solved_return(problem, board).map(|rest| rest - self.potential(problem, board))
+    }
+}
+
+/// another model minus `penalty` for every isolated peg after the jump, a peg without a neighbour to jump over
+pub struct IsolatedPegPenalty {
+    pub base: Box<dyn RewardModel>,
+    pub penalty: f64,
+}
+
+impl RewardModel for IsolatedPegPenalty {
+    fn name(&self) -> String {
+        format!("{} with a penalty of {} per isolated peg", self.base.name(), self.penalty)
+    }
+
+    fn reward(&self, problem: &Problem, board: Bitboard, next: Bitboard) -> f64 {
+        self.base.reward(problem, board, next) - self.penalty * IsolatedPegs.score(problem, next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peg_solitaire_environment::SolitaireAction;
+
+    // the rewards of the jumps of a game from the start of the problem
+    fn rewards(problem: &Problem, model: &dyn RewardModel, game: &[SolitaireAction]) -> Vec<f64> {
+        let mut board = problem.start.board;
+        game.iter()
+            .map(|action| {
+                let next = problem.layout().apply(board, *action);
+                let reward = model.reward(problem, board, next);
+                board = next;
+                reward
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reward_models() {
+        let problem = Problem::central_game();
+        let solution = crate::solver::solve(&problem).unwrap();
+        let total = |model: &dyn RewardModel| rewards(&problem, model, &solution).iter().sum::<f64>();
+        assert_eq!(total(&PegsRemoved), 31.);
+        assert_eq!(total(&TerminalSuccess), 1.);
+        assert_eq!(total(&DEFAULT_REWARDS), 31. + SOLVED_BONUS);
+        for model in [&PegsRemoved as &dyn RewardModel, &TerminalSuccess, &DEFAULT_REWARDS] {
+            assert_eq!(model.solved_return(&problem, problem.start.board), Some(total(model)));
+        }
+
+        // the shaping of a game only depends on its start
+        let shaping = PagodaShaping::new(&problem, Box::new(DEFAULT_REWARDS), 0.5);
+        assert!(!shaping.pagodas.is_empty());
+        let start = shaping.potential(&problem, problem.start.board);
+        assert!(start > 0.);
+        assert!((total(&shaping) - (31. + SOLVED_BONUS - start)).abs() < 1e-9);
+        assert_eq!(shaping.solved_return(&problem, problem.start.board), Some(31. + SOLVED_BONUS - start));
+        let shaped = rewards(&problem, &shaping, &solution);
+        assert!(shaped.iter().any(|r| *r != 1.));
+
+        // the last peg of a solution is isolated
+        let penalty = IsolatedPegPenalty { base: Box::new(PegsRemoved), penalty: 0.5 };
+        assert_eq!(*rewards(&problem, &penalty, &solution).last().unwrap(), 0.5);
+        assert_eq!(penalty.solved_return(&problem, problem.start.board), None);
+        assert!(total(&penalty) < 31.);
+    }
+}
@@ -0,0 +1,460 @@
+use crate::mcts::{Mcts, MctsConfig, Rollout, Selection};
+use crate::network::{Network, Optimizer, Sample};
+use crate::peg_solitaire_environment::{Bitboard, Jump, Point, Solitaire, SolitaireAction};
+use crate::problem::Problem;
+use crate::reward::{RewardModel, DEFAULT_REWARDS};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug)]
+pub struct SelfPlayConfig {
+    /// the search before every jump, it takes its priors and leaf values from the network
+    pub mcts: MctsConfig,
+    /// games played by the best network in every iteration
+    pub games: usize,
+    /// a game starts after up to this many random jumps from the start of the problem, 0 always starts there
+    pub random_start: u32,
+    /// the first jumps of a game are drawn from the visits of the search, afterwards the most visited is taken
+    pub exploration_moves: usize,
+    pub buffer_size: usize,
+    pub batch_size: usize,
+    /// batches trained on after the games of an iteration
+    pub training_steps: usize,
+    pub learning_rate: f64,
+    /// games played by the trained and the best network to decide which one is kept
+    pub evaluation_games: usize,
+    pub seed: u64,
+}
+
+impl Default for SelfPlayConfig {
+    fn default() -> Self {
+        SelfPlayConfig {
+            mcts: MctsConfig { iterations: 100, exploration: 1.5, selection: Selection::Puct, ..Default::default() },
+            games: 20,
+            random_start: 0,
+            exploration_moves: 8,
+            buffer_size: 20_000,
+            batch_size: 64,
+            training_steps: 100,
+            learning_rate: 1e-3,
+            evaluation_games: 10,
+            seed: 0,
+        }
+    }
+}
+
+impl SelfPlayConfig {
+    /// whether the games can be played, which takes a search with at least one rollout per jump
+    pub fn check(&self) -> Result<(), String> {
+        self.mcts.check()
+    }
+}
+
+/// The positions of the latest games, the oldest are dropped once the buffer is full. A buffer without capacity
+/// keeps nothing.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayBuffer {
+    pub capacity: usize,
+    pub samples: VecDeque<Sample>,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> Self {
+        ReplayBuffer { capacity, samples: VecDeque::new() }
+    }
+
+    pub fn push(&mut self, sample: Sample) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// samples drawn uniformly with replacement
+    pub fn batch(&self, size: usize, rng: &mut StdRng) -> Vec<Sample> {
+        (0..size).map(|_| self.samples[rng.gen_range(0..self.samples.len())].clone()).collect()
+    }
+
+    /// one line per sample: the board in hex, the value and the jumps as column, row and direction with their probability
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut s = String::new();
+        for sample in self.samples.iter() {
+            let policy: Vec<String> =
+                sample.policy.iter().map(|(a, p)| format!("{},{},{}:{}", a.point.x, a.point.y, a.action as usize, p)).collect();
+            writeln!(&mut s, "{:x}\t{}\t{}", sample.board.0, sample.value, policy.join(" "))?;
+        }
+        Ok(fs::write(path, s)?)
+    }
+
+    /// read a buffer written by `save`, keeping the latest `capacity` samples, every board and jump has to be one
+    /// the network can be trained on
+    pub fn load(path: &Path, capacity: usize, network: &Network) -> Result<Self, Box<dyn Error>> {
+        let mut buffer = ReplayBuffer::new(capacity);
+        for line in fs::read_to_string(path)?.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 3 {
+                return Err(format!("malformed sample {:?}", line).into());
+            }
+            let mut policy = Vec::new();
+            for jump in fields[2].split_whitespace() {
+                let (action, p) = jump.split_once(':').ok_or("a jump without probability")?;
+                let action: Vec<&str> = action.split(',').collect();
+                if action.len() != 3 {
+                    return Err(format!("malformed jump {:?}", jump).into());
+                }
+                let (point, jump) = (Point { x: action[0].parse()?, y: action[1].parse()? }, action[2].parse()?);
+                if jump > Jump::DownLeft as usize {
+                    return Err(format!("malformed jump {:?}", jump).into());
+                }
+                let action = SolitaireAction { point, action: Jump::from(jump) };
+                if network.action_index(action).is_none() {
+                    return Err(format!("the jump {:?} is not one of the board", action).into());
+                }
+                policy.push((action, p.parse()?));
+            }
+            let board = Bitboard(u128::from_str_radix(fields[0], 16)?);
+            if board.0 & !network.layout.mask != 0 {
+                return Err(format!("the board {:x} has pegs off the board", board.0).into());
+            }
+            buffer.push(Sample { board, value: fields[1].parse()?, policy });
+        }
+        Ok(buffer)
+    }
+}
+
+/// A game of self-play with what the network should learn from it.
+#[derive(Clone, Debug)]
+pub struct SelfPlayGame {
+    /// every position of the game with the visit distribution of its search and the rewards which followed
+    pub samples: Vec<Sample>,
+    pub end: Bitboard,
+    pub solved: bool,
+    /// the sum of the rewards of the jumps of the game
+    pub reward: f64,
+}
+
+/// Play a game from `start` to the target of the problem, searching with the network as policy and leaf value,
+/// or tell why the search can't play it.
+pub fn play_game(
+    problem: &Problem,
+    rewards: &dyn RewardModel,
+    network: &Network,
+    mcts: MctsConfig,
+    start: Bitboard,
+    exploration_moves: usize,
+    seed: u64,
+) -> Result<SelfPlayGame, String> {
+    let layout = problem.layout();
+    let game = Problem::new(Solitaire::from_layout(layout.clone(), start), problem.target);
+    let mut search = Mcts::new(&game, MctsConfig { seed, ..mcts }, Box::new(network.clone()), Rollout::Value(Box::new(network.clone())))?;
+    search.rewards = rewards;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut positions = Vec::new();
+    let mut history = Vec::new();
+    while !layout.is_finished(search.board()) {
+        for _ in 0..mcts.iterations {
+            search.iterate(&history);
+        }
+        let distribution = search.visit_distribution();
+        let action = match history.len() < exploration_moves {
+            true => distribution.choose_weighted(&mut rng, |(_, share)| *share).unwrap().0,
+            false => search.best_action().unwrap().0,
+        };
+        positions.push((search.board(), distribution));
+        history.push(action);
+        search.advance(action);
+    }
+    let end = search.board();
+    // the rewards still to come from every position, summed up from the end of the game
+    let mut reward = 0.;
+    let mut samples = Vec::new();
+    for ((board, policy), action) in positions.into_iter().zip(history).rev() {
+        reward += rewards.reward(&game, board, layout.apply(board, action));
+        samples.push(Sample { board, value: reward, policy });
+    }
+    samples.reverse();
+    Ok(SelfPlayGame { samples, end, solved: problem.is_solved(end), reward })
+}
+
+/// What happened in an iteration of `SelfPlay`, a line of the metrics file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IterationStats {
+    pub iteration: usize,
+    pub games: usize,
+    pub solved: usize,
+    pub mean_pegs: f64,
+    /// samples in the replay buffer after the games
+    pub samples: usize,
+    /// the average loss of the training batches
+    pub loss: f64,
+    /// the average reward of the evaluation games of the trained and of the best network
+    pub candidate_reward: f64,
+    pub best_reward: f64,
+    /// whether the trained network became the best one
+    pub accepted: bool,
+}
+
+impl IterationStats {
+    pub const HEADER: &'static str = "iteration\tgames\tsolved\tmean_pegs\tsamples\tloss\tcandidate_reward\tbest_reward\taccepted";
+
+    pub fn tsv(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{:.3}\t{}\t{:.5}\t{:.3}\t{:.3}\t{}",
+            self.iteration, self.games, self.solved, self.mean_pegs, self.samples, self.loss, self.candidate_reward, self.best_reward, self.accepted
+        )
+    }
+}
+
+/// AlphaZero style training: the best network plays games guided by MCTS, a copy of it is trained on the
+/// positions of the latest games and replaces the best network if it plays at least as well. The networks, the
+/// replay buffer and the metrics are written to a directory after every iteration, and a run on a directory
+/// which has them already goes on where it stopped. Adam starts over with fresh moments then.
+pub struct SelfPlay<'a> {
+    pub problem: &'a Problem,
+    pub config: SelfPlayConfig,
+    pub dir: PathBuf,
+    pub best: Network,
+    /// the network which is trained
+    pub candidate: Network,
+    pub buffer: ReplayBuffer,
+    /// how the jumps of the games are rewarded, `DEFAULT_REWARDS` unless set
+    pub rewards: &'a dyn RewardModel,
+    /// iterations finished so far, including the ones of earlier runs on the directory
+    pub iteration: usize,
+    optimizer: Optimizer,
+    rng: StdRng,
+}
+
+impl<'a> SelfPlay<'a> {
+    /// Start a new run with networks of the given hidden layers, or resume the run in the directory, which keeps
+    /// the hidden layers of its networks.
+    pub fn new(problem: &'a Problem, config: SelfPlayConfig, dir: &Path, hidden: &[usize]) -> Result<Self, Box<dyn Error>> {
+        config.check()?;
+        fs::create_dir_all(dir)?;
+        let layout = problem.layout();
+        let (best, candidate, buffer, iteration) = match dir.join("best.bin").exists() {
+            true => {
+                let best = Network::load(&dir.join("best.bin"), layout.clone())?;
+                let candidate = match dir.join("latest.bin").exists() {
+                    true => Network::load(&dir.join("latest.bin"), layout.clone())?,
+                    false => best.clone(),
+                };
+                let buffer = match dir.join("buffer.tsv").exists() {
+                    true => ReplayBuffer::load(&dir.join("buffer.tsv"), config.buffer_size, &candidate)?,
+                    false => ReplayBuffer::new(config.buffer_size),
+                };
+                let iterations = match dir.join("metrics.tsv").exists() {
+                    true => fs::read_to_string(dir.join("metrics.tsv"))?.lines().skip(1).count(),
+                    false => 0,
+                };
+                (best, candidate, buffer, iterations)
+            }
+            false => {
+                let best = Network::new(layout.clone(), hidden, config.seed);
+                best.save(&dir.join("best.bin"))?;
+                fs::write(dir.join("metrics.tsv"), format!("{}\n", IterationStats::HEADER))?;
+                (best.clone(), best, ReplayBuffer::new(config.buffer_size), 0)
+            }
+        };
+        Ok(SelfPlay {
+            problem,
+            config,
+            dir: dir.to_path_buf(),
+            best,
+            candidate,
+            buffer,
+            rewards: &DEFAULT_REWARDS,
+            iteration,
+            optimizer: Optimizer::adam(config.learning_rate),
+            // a resumed run doesn't play the games of the first iterations again
+            rng: StdRng::seed_from_u64(config.seed.wrapping_add(iteration as u64)),
+        })
+    }
+
+    // a position after a random number of random jumps from the start which is not finished yet
+    fn random_start(&self, rng: &mut StdRng) -> Bitboard {
+        let layout = self.problem.layout();
+        let mut board = self.problem.start.board;
+        for _ in 0..rng.gen_range(0..=self.config.random_start) {
+            let next = match layout.actions(board).choose(rng) {
+                Some(action) => layout.apply(board, *action),
+                None => break,
+            };
+            if layout.is_finished(next) {
+                break;
+            }
+            board = next;
+        }
+        board
+    }
+
+    /// Play the games of an iteration, train the candidate, let it compete with the best network and write
+    /// everything to the directory.
+    pub fn iterate(&mut self) -> Result<IterationStats, Box<dyn Error>> {
+        let mut stats = IterationStats { iteration: self.iteration + 1, games: self.config.games, ..Default::default() };
+        let mut rng = StdRng::seed_from_u64(self.rng.gen());
+        let mut pegs = 0;
+        for _ in 0..self.config.games {
+            let start = self.random_start(&mut rng);
+            let game = play_game(self.problem, self.rewards, &self.best, self.config.mcts, start, self.config.exploration_moves, rng.gen())?;
+            stats.solved += game.solved as usize;
+            pegs += game.end.count_pegs();
+            game.samples.into_iter().for_each(|sample| self.buffer.push(sample));
+        }
+        stats.mean_pegs = pegs as f64 / self.config.games.max(1) as f64;
+        stats.samples = self.buffer.len();
+
+        if !self.buffer.is_empty() {
+            for _ in 0..self.config.training_steps {
+                let batch = self.buffer.batch(self.config.batch_size, &mut rng);
+                stats.loss += self.candidate.train_batch(&batch, &mut self.optimizer);
+            }
+            stats.loss /= self.config.training_steps.max(1) as f64;
+        }
+
+        // both networks play from the same positions with the same seeds, the first game from the start
+        let evaluation: Vec<(Bitboard, u64)> = (0..self.config.evaluation_games)
+            .map(|idx| (if idx == 0 { self.problem.start.board } else { self.random_start(&mut rng) }, rng.gen()))
+            .collect();
+        let mean_reward = |network: &Network| -> Result<f64, String> {
+            let mut total = 0.;
+            for (start, seed) in evaluation.iter() {
+                total += play_game(self.problem, self.rewards, network, self.config.mcts, *start, 0, *seed)?.reward;
+            }
+            Ok(total / evaluation.len().max(1) as f64)
+        };
+        stats.candidate_reward = mean_reward(&self.candidate)?;
+        stats.best_reward = mean_reward(&self.best)?;
+        stats.accepted = stats.candidate_reward >= stats.best_reward;
+        if stats.accepted {
+            self.best = self.candidate.clone();
+        }
+
+        self.best.save(&self.dir.join("best.bin"))?;
+        self.candidate.save(&self.dir.join("latest.bin"))?;
+        self.buffer.save(&self.dir.join("buffer.tsv"))?;
+        let mut metrics = fs::OpenOptions::new().append(true).create(true).open(self.dir.join("metrics.tsv"))?;
+        writeln!(metrics, "{}", stats.tsv())?;
+        self.iteration += 1;
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem::Target;
+    use crate::test_problems::triangle;
+
+    #[test]
+    fn test_replay_buffer() {
+        let problem = triangle();
+        let network = Network::new(problem.layout().clone(), &[8], 0);
+        let game = play_game(&problem, &DEFAULT_REWARDS, &network, MctsConfig { iterations: 30, selection: Selection::Puct, ..Default::default() }, problem.start.board, 4, 1).unwrap();
+        assert_eq!(game.samples.len() as u32, problem.start.board.count_pegs() - game.end.count_pegs());
+        assert_eq!(game.samples[0].value, game.reward);
+        for sample in game.samples.iter() {
+            assert!((sample.policy.iter().map(|(_, p)| p).sum::<f64>() - 1.).abs() < 1e-12);
+        }
+
+        let mut buffer = ReplayBuffer::new(5);
+        game.samples.iter().cloned().for_each(|sample| buffer.push(sample));
+        assert_eq!(buffer.len(), 5);
+        assert_eq!(buffer.samples[4], *game.samples.last().unwrap());
+        let mut empty = ReplayBuffer::new(0);
+        game.samples.iter().cloned().for_each(|sample| empty.push(sample));
+        assert!(empty.is_empty());
+        let path = std::env::temp_dir().join(format!("selfplay_replay_buffer_{}.tsv", std::process::id()));
+        buffer.save(&path).unwrap();
+        let loaded = ReplayBuffer::load(&path, 3, &network).unwrap();
+        assert_eq!(loaded.samples, buffer.samples.iter().skip(2).cloned().collect::<VecDeque<_>>());
+        // jumps and pegs off the board are rejected
+        fs::write(&path, format!("{:x}\t1\t6,6,0:1\n", problem.start.board.0)).unwrap();
+        assert!(ReplayBuffer::load(&path, 3, &network).is_err());
+        let (action, _) = game.samples[0].policy[0];
+        let jump = format!("{},{},{}:1", action.point.x, action.point.y, action.action as usize);
+        fs::write(&path, format!("{:x}\t1\t{}\n", problem.start.board.0, jump)).unwrap();
+        assert!(ReplayBuffer::load(&path, 3, &network).is_ok());
+        fs::write(&path, format!("{:x}\t1\t{}\n", problem.start.board.0 | 1 << 127, jump)).unwrap();
+        assert!(ReplayBuffer::load(&path, 3, &network).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_selfplay_resumes() {
+        let problem = triangle();
+        let dir = std::env::temp_dir().join(format!("selfplay_resumes_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = SelfPlayConfig {
+            mcts: MctsConfig { iterations: 20, selection: Selection::Puct, ..Default::default() },
+            games: 3,
+            random_start: 3,
+            training_steps: 10,
+            batch_size: 16,
+            evaluation_games: 2,
+            ..Default::default()
+        };
+        let mut selfplay = SelfPlay::new(&problem, config, &dir, &[8]).unwrap();
+        for iteration in 1..=2 {
+            let stats = selfplay.iterate().unwrap();
+            assert_eq!(stats.iteration, iteration);
+            assert!(stats.loss.is_finite() && stats.loss > 0.);
+            assert!(stats.samples > 0 && stats.samples <= 2 * 3 * 13);
+        }
+        let (best, samples) = (selfplay.best.params.clone(), selfplay.buffer.len());
+
+        let mut resumed = SelfPlay::new(&problem, config, &dir, &[16, 16]).unwrap();
+        assert_eq!(resumed.iteration, 2);
+        assert_eq!(resumed.best.params, best);
+        assert_eq!(resumed.best.hidden, vec![8]);
+        assert_eq!(resumed.buffer.len(), samples);
+        assert_eq!(resumed.iterate().unwrap().iteration, 3);
+        let metrics = fs::read_to_string(dir.join("metrics.tsv")).unwrap();
+        assert_eq!(metrics.lines().count(), 4);
+        assert_eq!(metrics.lines().next().unwrap(), IterationStats::HEADER);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_random_start_of_a_finished_game() {
+        let layout = triangle().layout().clone();
+        let problem = Problem::new(Solitaire::from_layout(layout, Bitboard(Bitboard::bit(Point { x: 0, y: 0 }))), Target::AnySinglePeg);
+        let dir = std::env::temp_dir().join(format!("selfplay_finished_start_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let selfplay = SelfPlay::new(&problem, SelfPlayConfig { random_start: 3, ..Default::default() }, &dir, &[8]).unwrap();
+        for seed in 0..10 {
+            assert_eq!(selfplay.random_start(&mut StdRng::seed_from_u64(seed)), problem.start.board);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_zero_rollouts() {
+        let problem = triangle();
+        let network = Network::new(problem.layout().clone(), &[8], 0);
+        let mcts = MctsConfig { iterations: 0, ..Default::default() };
+        assert!(play_game(&problem, &DEFAULT_REWARDS, &network, mcts, problem.start.board, 4, 1).is_err());
+        let config = SelfPlayConfig { mcts, ..Default::default() };
+        let dir = std::env::temp_dir().join(format!("selfplay_zero_rollouts_{}", std::process::id()));
+        assert!(SelfPlay::new(&problem, config, &dir, &[8]).is_err());
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::board_geometry::BoardLayout;
+use crate::peg_solitaire_environment::{Bitboard, Point, SolitaireAction};
+use crate::problem::Problem;
+use crate::solver::solve;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// For every pair of cells whether the problem starting with a single hole on the first one and finishing
+/// with a single peg on the second one can be solved, together with a solution.
+#[derive(Debug)]
+pub struct SolvabilityTable {
+    pub layout: Arc<BoardLayout>,
+    /// `witnesses[h][s]` solves the problem from a hole on `layout.cells[h]` to a peg on `layout.cells[s]`
+    pub witnesses: Vec<Vec<Option<Vec<SolitaireAction>>>>,
+    /// number of problems which were checked, the remaining ones follow by symmetry
+    pub searched: usize,
+}
+
+impl SolvabilityTable {
+    fn cell_index(&self, p: Point) -> usize {
+        self.layout.cells.iter().position(|c| *c == p).expect("the point has to be a cell of the board")
+    }
+
+    pub fn witness(&self, hole: Point, survivor: Point) -> Option<&Vec<SolitaireAction>> {
+        self.witnesses[self.cell_index(hole)][self.cell_index(survivor)].as_ref()
+    }
+
+    pub fn is_solvable(&self, hole: Point, survivor: Point) -> bool {
+        self.witness(hole, survivor).is_some()
+    }
+
+    /// the cells on which the last peg can finish when starting with a hole on `hole`
+    pub fn survivors(&self, hole: Point) -> Bitboard {
+        let row = &self.witnesses[self.cell_index(hole)];
+        let bits = self.layout.cells.iter().zip(row.iter()).filter(|(_, w)| w.is_some()).fold(0, |bits, (p, _)| bits | Bitboard::bit(*p));
+        Bitboard(bits)
+    }
+
+    pub fn count_solvable(&self) -> usize {
+        self.witnesses.iter().flatten().filter(|w| w.is_some()).count()
+    }
+
+    /// the board with the number of finishing cells of every starting hole, followed by one line per
+    /// starting hole with its finishing cells as a position string
+    pub fn report(&self) -> String {
+        let n = self.layout.cells.len();
+        let mut s = String::new();
+        writeln!(
+            &mut s,
+            "{} of {} single vacancy to single survivor problems on the {} board are solvable, {} of them were checked",
+            self.count_solvable(), n * n, self.layout.name, self.searched
+        ).unwrap();
+        writeln!(&mut s, "\nNumber of finishing cells for every starting hole").unwrap();
+        for y in 0..self.layout.height {
+            let row: String = (0..self.layout.width)
+                .map(|x| match self.layout.is_valid(Point { x, y }) {
+                    true => format!("{:3}", self.survivors(Point { x, y }).count_pegs()),
+                    false => String::from("   "),
+                })
+                .collect();
+            writeln!(&mut s, "{}", row.trim_end()).unwrap();
+        }
+        writeln!(&mut s, "\nFinishing cells for every starting hole").unwrap();
+        for hole in self.layout.cells.iter() {
+            writeln!(&mut s, "{:?} {}", (hole.x, hole.y), self.layout.position_string(self.survivors(*hole))).unwrap();
+        }
+        s
+    }
+
+    /// one line for every solvable problem with the jumps of its solution
+    pub fn witnesses_report(&self) -> String {
+        let mut s = String::new();
+        for (hole, row) in self.layout.cells.iter().zip(self.witnesses.iter()) {
+            for (survivor, witness) in self.layout.cells.iter().zip(row.iter()) {
+                if let Some(witness) = witness {
+                    let jumps: Vec<String> = witness.iter().map(|a| format!("{:?} {:?}", (a.point.x, a.point.y), a.action)).collect();
+                    writeln!(&mut s, "{:?} -> {:?}: {}", (hole.x, hole.y), (survivor.x, survivor.y), jumps.join(", ")).unwrap();
+                }
+            }
+        }
+        s
+    }
+}
+
+/// Solve every single vacancy to single survivor problem of the board. Problems which are the image of an
+/// already solved one under a symmetry of the board are not searched again.
+pub fn solvability_table(layout: Arc<BoardLayout>) -> SolvabilityTable {
+    let cells = layout.cells.clone();
+    let n = cells.len();
+    let index: HashMap<Point, usize> = cells.iter().enumerate().map(|(idx, p)| (*p, idx)).collect();
+    let mut witnesses: Vec<Vec<Option<Vec<SolitaireAction>>>> = vec![vec![None; n]; n];
+    let mut done = vec![vec![false; n]; n];
+    let mut searched = 0;
+    for h in 0..n {
+        for s in 0..n {
+            if done[h][s] {
+                continue;
+            }
+            let problem = Problem::single_vacancy(layout.clone(), cells[h], cells[s]);
+            let witness = solve(&problem);
+            searched += 1;
+            for idx in 0..layout.symmetries.len() {
+                let t = &layout.symmetries[idx];
+                let (th, ts) = (index[&t.apply(cells[h])], index[&t.apply(cells[s])]);
+                if !done[th][ts] {
+                    done[th][ts] = true;
+                    witnesses[th][ts] = witness.as_ref().map(|w| w.iter().map(|a| layout.transform_action(*a, idx)).collect());
+                }
+                // playing the same jumps backwards on the complement of each position solves the problem
+                // from a hole on the survivor to a peg on the starting hole
+                if !done[ts][th] {
+                    done[ts][th] = true;
+                    witnesses[ts][th] = witnesses[th][ts].as_ref().map(|w| w.iter().rev().copied().collect());
+                }
+            }
+        }
+    }
+    SolvabilityTable { layout, witnesses, searched }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::Triangle;
+
+    #[test]
+    fn test_solvability_table_triangle() {
+        let layout = Arc::new(BoardLayout::new(&Triangle::CRACKER_BARREL));
+        let table = solvability_table(layout.clone());
+        // with 6 symmetries and the reversed problems only a few of the 225 problems have to be checked
+        assert!(table.searched < 50);
+
+        for (h, hole) in layout.cells.iter().enumerate() {
+            // the puzzle can be finished with a single peg from every starting hole
+            assert!(table.survivors(*hole).count_pegs() > 0);
+            for (s, survivor) in layout.cells.iter().enumerate() {
+                assert_eq!(table.is_solvable(*hole, *survivor), table.is_solvable(*survivor, *hole));
+                if let Some(witness) = &table.witnesses[h][s] {
+                    let mut board = layout.single_vacancy(*hole);
+                    for action in witness {
+                        assert!(layout.actions(board).contains(action));
+                        board = layout.apply(board, *action);
+                    }
+                    assert_eq!(board, Bitboard(Bitboard::bit(*survivor)));
+                }
+            }
+        }
+        assert!(table.report().starts_with(&format!("{} of 225 single vacancy", table.count_solvable())));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::pagoda::PagodaPruning;
+use crate::peg_solitaire_environment::{Bitboard, SolitaireAction};
+use crate::problem::Problem;
+use std::collections::HashSet;
+use std::hash::{BuildHasherDefault, Hasher};
+
+/// Counters of a search.
+#[derive(Clone, Debug, Default)]
+pub struct SearchStats {
+    /// positions which were expanded
+    pub nodes: u64,
+    /// positions which were proven to not lead to the target
+    pub dead_positions: usize,
+    /// the pagodas which were used and how many positions each of them ruled out
+    pub pagodas: PagodaPruning,
+    /// why the problem was rejected without searching
+    pub infeasible: Option<String>,
+}
+
+/// Depth first search for a solution of the problem, returning the jumps of the first one found.
+pub fn solve(problem: &Problem) -> Option<Vec<SolitaireAction>> {
+    solve_with_stats(problem).0
+}
+
+/// search with the built in pagodas of the board
+pub fn solve_with_stats(problem: &Problem) -> (Option<Vec<SolitaireAction>>, SearchStats) {
+    solve_with_pagodas(problem, PagodaPruning::for_problem(problem))
+}
+
+/// search without visiting positions which one of the pagodas rules out
+pub fn solve_with_pagodas(problem: &Problem, pagodas: PagodaPruning) -> (Option<Vec<SolitaireAction>>, SearchStats) {
+    if let Err(reason) = problem.check_feasible() {
+        return (None, SearchStats { pagodas, infeasible: Some(reason), ..Default::default() });
+    }
+    let mut search = DepthFirstSearch {
+        problem,
+        pagodas,
+        symmetries: problem.symmetries(),
+        dead: PositionSet::default(),
+        moves: Vec::new(),
+        nodes: 0,
+    };
+    let solved = search.search(problem.start.board);
+    let stats = SearchStats { nodes: search.nodes, dead_positions: search.dead.len(), pagodas: search.pagodas, infeasible: None };
+    match solved {
+        true => (Some(search.moves), stats),
+        false => (None, stats),
+    }
+}
+
+// The keys are compressed positions which are already well mixed, so a single multiplication is
+// enough to hash them and is a lot faster than the default hasher.
+#[derive(Default)]
+pub struct PositionHasher(u64);
+
+impl Hasher for PositionHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u64(*byte as u64);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = (self.0.rotate_left(5) ^ value).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+pub type PositionSet = HashSet<u64, BuildHasherDefault<PositionHasher>>;
+
+// Every position which was fully searched without reaching the target is remembered, compressed to one
+// bit per cell and identified with the positions symmetric to it under the symmetries of the problem.
+struct DepthFirstSearch<'a> {
+    problem: &'a Problem,
+    pagodas: PagodaPruning,
+    symmetries: Vec<usize>,
+    dead: PositionSet,
+    moves: Vec<SolitaireAction>,
+    nodes: u64,
+}
+
+impl DepthFirstSearch<'_> {
+    fn search(&mut self, board: Bitboard) -> bool {
+        if self.problem.is_solved(board) {
+            return true;
+        }
+        if board.count_pegs() <= self.problem.target.pegs() {
+            return false;
+        }
+        let layout = self.problem.layout();
+        let key = layout.compress(layout.canonical_key_among(board, &self.symmetries).board());
+        if self.dead.contains(&key) || self.pagodas.prunes(board) {
+            return false;
+        }
+        self.nodes += 1;
+        for jump in layout.jumps.iter() {
+            let mut movable = layout.movable_pegs(board, *jump);
+            while movable != 0 {
+                let action = SolitaireAction { point: Bitboard::point(movable.trailing_zeros()), action: *jump };
+                self.moves.push(action);
+                if self.search(layout.apply(board, action)) {
+                    return true;
+                }
+                self.moves.pop();
+                movable &= movable - 1;
+            }
+        }
+        self.dead.insert(key);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::{BoardLayout, Triangle};
+    use crate::peg_solitaire_environment::{Point, Solitaire};
+    use crate::problem::Target;
+    use crate::test_problems::{assert_solves, english_endgame};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_solve_triangle() {
+        let layout = Arc::new(BoardLayout::new(&Triangle::CRACKER_BARREL));
+        let problem = Problem::complement(layout.clone(), Point { x: 0, y: 2 });
+        let (solution, stats) = solve_with_stats(&problem);
+        let solution = solution.unwrap();
+        assert_eq!(solution.len(), 13);
+        assert_solves(&problem, &solution);
+        assert!(stats.nodes >= 13);
+
+        let problem = Problem::new(Solitaire::with_geometry(&Triangle::CRACKER_BARREL), Target::AnySinglePeg);
+        assert_solves(&problem, &solve(&problem).unwrap());
+    }
+
+    #[test]
+    fn test_unsolvable_problem() {
+        // on the triangle with 10 holes a single peg can not be left when starting at a corner
+        let layout = Arc::new(BoardLayout::new(&Triangle::new(4)));
+        let problem = Problem::new(Solitaire::from_layout(layout.clone(), layout.start_position()), Target::AnySinglePeg);
+        let (solution, stats) = solve_with_stats(&problem);
+        assert_eq!(solution, None);
+        assert_eq!(stats.nodes as usize, stats.dead_positions);
+    }
+
+    #[test]
+    fn test_solve_english_endgame() {
+        let problem = english_endgame();
+        let solution = solve(&problem).unwrap();
+        assert_eq!(solution.len(), 15);
+        assert_solves(&problem, &solution);
+    }
+
+    #[test]
+    fn test_solve_central_game() {
+        let problem = Problem::central_game();
+        let solution = solve(&problem).unwrap();
+        assert_eq!(solution.len(), 31);
+        assert_solves(&problem, &solution);
+        let end = solution.iter().fold(problem.start.board, |board, action| problem.layout().apply(board, *action));
+        assert_eq!(end, Bitboard(Bitboard::bit(Point { x: 3, y: 3 })));
+    }
+
+    #[test]
+    fn test_reject_infeasible_problem() {
+        let problem = Problem::single_vacancy(BoardLayout::english(), Point { x: 3, y: 3 }, Point { x: 3, y: 2 });
+        let (solution, stats) = solve_with_stats(&problem);
+        assert_eq!(solution, None);
+        assert_eq!(stats.nodes, 0);
+        assert!(stats.infeasible.unwrap().contains("position class"));
+    }
+
+    #[test]
+    fn test_pagodas_prune_the_search() {
+        let problem = english_endgame();
+        let (plain, plain_stats) = solve_with_pagodas(&problem, PagodaPruning::default());
+        let (pruned, stats) = solve_with_stats(&problem);
+        assert_eq!(plain.is_some(), pruned.is_some());
+        assert_eq!(plain_stats.pagodas.total_pruned(), 0);
+        assert!(stats.pagodas.total_pruned() > 0);
+        assert!(stats.nodes < plain_stats.nodes);
+        assert_solves(&problem, &pruned.unwrap());
+    }
+
+    #[test]
+    fn test_solve_exact_target() {
+        let problem = Problem::central_game();
+        let mut env = problem.new_game();
+        let actions = env.actions().unwrap();
+        env.take_action(&actions[0].value());
+        let target = Problem::new(problem.new_game(), Target::Board(env.board));
+        assert_eq!(solve(&target), Some(vec![actions[0]]));
+    }
+}
@@ -1,19 +1,48 @@
-use crate::peg_solitaire_environment::{StateT, ActionT, Solitaire, SolitaireState};
+use crate::pagoda::PagodaPruning;
+use crate::peg_solitaire_environment::{Bitboard, CanonicalKey, Solitaire};
+use crate::problem::Problem;
+use crate::retrograde::Tablebase;
+use crate::reward::{RewardModel, DEFAULT_REWARDS};
 use std::collections::HashMap;
 
-#[derive(Debug)]
+/// extra reward for a game which reaches the target of the problem
+pub const SOLVED_BONUS: f64 = 10.;
+
+/// An estimate of the rewards still to come from a position, learned by adjusting parameters. A table has one
+/// parameter per position, an approximation shares its parameters between positions. Finished positions are
+/// worth nothing, the learners never ask for their value.
+pub trait ValueFunction {
+    fn name(&self) -> String;
+    fn value(&self, board: Bitboard) -> f64;
+    /// the parameters the value of the position depends on, with the derivative of the value by each of them
+    fn gradient(&self, board: Bitboard) -> Vec<(u64, f64)>;
+    fn add_to_parameter(&mut self, parameter: u64, delta: f64);
+}
+
 pub struct StateFunction {
-    pub qs: HashMap<String, (i32, f64, String)>,
+    pub qs: HashMap<CanonicalKey, (i32, f64, String)>,
+    /// positions which these pagodas rule out are not played on but scored like finished games
+    pub pagodas: Option<PagodaPruning>,
+    /// positions in this endgame table get their exact value instead of being played on
+    pub tablebase: Option<Tablebase>,
+    /// how the jumps of a game are rewarded, `DEFAULT_REWARDS` unless set
+    pub rewards: Box<dyn RewardModel>,
+}
+
+impl Default for StateFunction {
+    fn default() -> Self {
+        StateFunction::new()
+    }
 }
 
 impl StateFunction {
     pub fn new() -> Self {
-        let hash: HashMap<String, (i32, f64, String)> = HashMap::new();
-        StateFunction { qs: hash }
+        let hash: HashMap<CanonicalKey, (i32, f64, String)> = HashMap::new();
+        StateFunction { qs: hash, pagodas: None, tablebase: None, rewards: Box::new(DEFAULT_REWARDS) }
     }
 
     pub fn update_state_value_with_fn<F>(&mut self, 
-                              state_hash: String,
+                              state_hash: CanonicalKey,
                               state_string: String,
                               fun: F ,
                               value: f64
@@ -35,22 +64,19 @@ impl StateFunction {
     }
 
     pub fn get_state_value(&self, 
-                           state_hash: &String) -> Option<f64> {
-        match self.qs.get(state_hash) {
-            Some(value) => Some(value.1.clone()),
-            None => None
-        }
+                           state_hash: &CanonicalKey) -> Option<f64> {
+        self.qs.get(state_hash).map(|value| value.1)
     }
 
     pub fn get_state_counter(&self,
-                             state_hash: &String) -> i32 {
+                             state_hash: &CanonicalKey) -> i32 {
         match self.qs.get(state_hash) {
-            Some(value) => value.0.clone(),
+            Some(value) => value.0,
             None => 0
         }
     }
 
-    pub fn get_least_seen_state(&self, state_hashes: Vec<&String>) -> String {
+    pub fn get_least_seen_state(&self, state_hashes: Vec<&CanonicalKey>) -> CanonicalKey {
         let mut least_seen_state = state_hashes[0];
         let mut counter = i32::MAX;
 
@@ -58,54 +84,43 @@ impl StateFunction {
             let c = self.get_state_counter(state);
             // don't waste time, take this state immediately
             if c == 0 {
-                return (*state).to_string()
+                return **state
             }
             if c < counter {
                 least_seen_state = state;
                 counter = c;
             }
         }
-        (*least_seen_state).to_string()
+        *least_seen_state
     }
 
     pub fn update_reward_and_logging(&mut self, 
-                                     state: SolitaireState, 
-                                     visited_hashes: Vec<String>, 
+                                     visited_hashes: Vec<CanonicalKey>, 
                                      visited_states: Vec<String>, 
                                      reward: f64, 
                                      iterations: &mut i128) {
-        let reward_entry = match visited_hashes[visited_hashes.len() - 1].as_str() {
-            "3_4.650282_5.650282_1370.759762_1433784" => reward + 10.,
-            // commented out on 29-11
-            // "32_1565.69579_72.843619_0_72.843619_1000000" => reward + 10.,
-            // "this is the simple case"
-            // "32_1565.69579_72.843619" => reward + 10.,
-            _ => reward,
-        };
         assert_eq!(visited_hashes.len(), visited_states.len());
         for (hash, state_string) in visited_hashes.iter().zip(visited_states.iter()) {
-            self.update_state_value_with_fn(hash.clone(), state_string.clone(), f64::max, reward_entry);
+            self.update_state_value_with_fn(*hash, state_string.clone(), f64::max, reward);
         }
         *iterations += 1;
         // println!("EVERYTHING DONE: This is env\n{}", Solitaire::from_state(state));
-        if ((*iterations) % 1_000_000 == 0) {
+        if (*iterations) % 1_000_000 == 0 {
             println!("Reached {} iterations, visited {} positions", iterations, self.qs.len());
         }
     }
 
-    pub fn iterate_game(&mut self, state: SolitaireState, mut visited_hashes: Vec<String>, mut visited_states: Vec<String>, reward: f64, iterations: &mut i128) {
-        let env = Solitaire::from_state(state);
-        // println!("START OF FUNCTION: This is env\n{}", Solitaire::from_state(state.clone()));
+    pub fn iterate_game(&mut self, problem: &Problem, env: &Solitaire, mut visited_hashes: Vec<CanonicalKey>, mut visited_states: Vec<String>, reward: f64, iterations: &mut i128) {
+        // println!("START OF FUNCTION: This is env\n{}", Solitaire::from_state(state));
         // println!("START OF FUNCTION: These are hashes: {:?}", visited_hashes);
-        let current_hash = env.hash_as_str();
+        let current_hash = problem.canonical_key(env.board);
 
-        visited_hashes.push(current_hash.clone());
-        visited_states.push(state.to_string());
+        visited_hashes.push(current_hash);
+        visited_states.push(env.layout.position_string(env.board));
         // weitere opt möglichkeit: check ob hash in der state function ist, wenn ja, füge allen vorherigen states
         // den gleichen wert hinzu
-        if self.qs.get(&current_hash).is_some() {
+        if let Some(&(_, reward, _)) = self.qs.get(&current_hash) {
 
-           let (num, reward, _) = self.qs.get(&current_hash).unwrap();
            //if visited_hashes.iter().any(|l| l.clone() == String::from("21_667.271386_47.405942")) {
            //    println!("");
            //    for (s, h) in visited_states.iter().zip(visited_hashes.iter()) {
@@ -113,44 +128,34 @@ impl StateFunction {
            //    }
            //    println!("");
            //}
-           self.update_reward_and_logging(state, visited_hashes, visited_states, *reward, iterations);
+           self.update_reward_and_logging(visited_hashes, visited_states, reward, iterations);
 
         }
+        else if let Some(value) = self.tablebase.as_ref().and_then(|tablebase| tablebase.value(problem, self.rewards.as_ref(), env.board, reward)) {
+            self.update_reward_and_logging(visited_hashes, visited_states, value, iterations);
+        }
+        else if self.pagodas.as_mut().is_some_and(|pagodas| pagodas.prunes(env.board)) {
+            // the target can't be reached from here, so the game is dropped without a value for its positions,
+            // which keeps the values of the positions that can reach it the same as without pruning
+        }
         else {
-        let actions = env.get_symmetry_reduced_actions();
-            match actions {
-                Some(actions) => {
+        let actions = problem.symmetry_reduced_actions(env);
+            match actions.is_empty() {
+                false => {
                     // println!("We iterate over {} actions", actions.len());
                     // wir iterieren hier über alle möglichkeiten ohne die Symmetrie zu beachten -> schreibe env.get_symmetry_reduced_actions()
                     // vllt ist ein check auch noch hilfreich, denn wenn ein hash schon den max wert hat, wird dieser nicht mehr
                     // weiter verbessert
                     for action in actions {
-                        let (state, _, _) = env.simulate_action(&action.value());
-                        // println!("DURING ITERATION: This is env\n{}", Solitaire::from_state(state.clone()));
-                        self.iterate_game(state, visited_hashes.clone(), visited_states.clone(), reward + 1., iterations);
+                        let next = env.with_board(env.simulate_action(&action.value()));
+                        // println!("DURING ITERATION: This is env\n{}", Solitaire::from_state(state));
+                        let jump_reward = self.rewards.reward(problem, env.board, next.board);
+                        self.iterate_game(problem, &next, visited_hashes.clone(), visited_states.clone(), reward + jump_reward, iterations);
                     }
                 },
-                None => {
-                    // if visited_hashes.iter().any(|l| l.clone() == String::from("21_667.271386_47.405942")) {
-                    //     println!("");
-                    //     for (s, h) in visited_states.iter().zip(visited_hashes.iter()) {
-                    //         println!("This is a state {} and hash {}", s, h);
-                    //     }
-                    //     println!("");
-                    // }
-                    self.update_reward_and_logging(state, visited_hashes, visited_states, reward, iterations);
-                    // let reward_entry = match env.hash_as_str().as_str() {
-                    //     "32_1565.69579_72.843619" => reward + 10.,
-                    //     _ => reward,
-                    // };
-                    // for hash in visited_hashes {
-                    //     self.update_state_value_with_fn(&hash, f64::max, reward_entry);
-                    // }
-                    // *iterations += 1;
-                    // println!("EVERYTHING DONE: This is env\n{}", Solitaire::from_state(state));
-                    // if ((*iterations) % 100_000 == 0) {
-                    //     println!("Reached {} iterations, visited {} positions", iterations, self.qs.len());
-                    // }
+                true => {
+                    // the reward of the last jump already has the one for the end of the game
+                    self.update_reward_and_logging(visited_hashes, visited_states, reward, iterations);
                 }
             }
         }
@@ -158,10 +163,14 @@ impl StateFunction {
 }
 
 
+#[cfg(test)]
 mod tests {
     use super::*;
-    // use crate::peg_solitaire_environment::{SolitaireState, Solitaire};
-    use crate::peg_solitaire_environment::{StateT, ActionT, Solitaire, SolitaireState};
+    use crate::board_geometry::{English, RuleSet, Triangle};
+    use crate::peg_solitaire_environment::{Bitboard, Point};
+    use crate::problem::Target;
+    use crate::peg_solitaire_environment::SolitaireState;
+    use crate::test_problems::english_endgame;
 
     #[test]
     fn test_iterate_game_single_moves() {
@@ -177,9 +186,8 @@ mod tests {
                 [-1, -1, 1, 0, 0, -1, -1]
                 ]
         };
-        let env = Solitaire::from_state(state.clone());
         let mut state_function = StateFunction::new();
-        state_function.iterate_game(state.clone(), vec![], vec![], 20., &mut 0);
+        state_function.iterate_game(&Problem::central_game(), &Solitaire::from_state(state), vec![], vec![], 20., &mut 0);
         // [[-1, -1, 1, 0, 0, -1, -1],
         // [-1, -1, 1, 0, 0, -1, -1],
         // [1, 1, 1, 1, 0, 1, 1],
@@ -212,10 +220,10 @@ mod tests {
                 [-1, -1, 1, 0, 0, -1, -1]
                 ]
         };
-        let env = Solitaire::from_state(state.clone());
+        let env = Solitaire::from_state(state);
         let mut state_function = StateFunction::new();
-        state_function.iterate_game(state.clone(), vec![], vec![], 20., &mut 0);
-        println!("This is the qs value of the start state {:?}", state_function.qs.get(&env.hash_as_str()));
+        state_function.iterate_game(&Problem::central_game(), &Solitaire::from_state(state), vec![], vec![], 20., &mut 0);
+        println!("This is the qs value of the start state {:?}", state_function.qs.get(&env.canonical_key()));
     }
 
     // #[test]
@@ -231,11 +239,11 @@ mod tests {
     //             [-1, -1, 1, 1, 1, -1, -1],
     //         ],
     //     };
-    //     let env = Solitaire::from_state(state.clone());
+    //     let env = Solitaire::from_state(state);
     //     let mut state_function = StateFunction::new();
-    //     state_function.iterate_game(state, vec![], vec![], 0., &mut 0);
+    //     state_function.iterate_game(&Problem::central_game(), &Solitaire::from_state(state), vec![], vec![], 0., &mut 0);
     //     
-    //     println!("other: {:?}", state_function.qs.get(&env.hash_as_str()));
+    //     println!("other: {:?}", state_function.qs.get(&env.canonical_key()));
     //     println!("LEN OF state function: {}", state_function.qs.len());
     //     for (h, s) in state_function.qs.iter() {
     //         println!("Hash {}\tvisits {}\tvalue {}", h, s.0, s.1);
@@ -256,11 +264,11 @@ mod tests {
                 [-1, -1, 0, 0, 0, -1, -1],
             ],
         };
-        let env = Solitaire::from_state(state.clone());
+        let env = Solitaire::from_state(state);
         let mut state_function = StateFunction::new();
-        state_function.iterate_game(state, vec![], vec![], 26., &mut 0);
+        state_function.iterate_game(&Problem::central_game(), &Solitaire::from_state(state), vec![], vec![], 26., &mut 0);
         
-        println!("other: {:?}", state_function.qs.get(&env.hash_as_str()));
+        println!("other: {:?}", state_function.qs.get(&env.canonical_key()));
         println!("LEN OF state function: {}", state_function.qs.len());
         for (h, s) in state_function.qs.iter() {
             println!("Hash {}\tvisits {}\tvalue {}", h, s.0, s.1);
@@ -281,11 +289,11 @@ mod tests {
                 [-1, -1, 0, 0, 0, -1, -1],
             ],
         };
-        let env = Solitaire::from_state(state.clone());
+        let env = Solitaire::from_state(state);
         let mut state_function = StateFunction::new();
-        state_function.iterate_game(state, vec![], vec![], 25., &mut 0);
+        state_function.iterate_game(&Problem::central_game(), &Solitaire::from_state(state), vec![], vec![], 25., &mut 0);
         
-        println!("other: {:?}", state_function.qs.get(&env.hash_as_str()));
+        println!("other: {:?}", state_function.qs.get(&env.canonical_key()));
         println!("other: {:?}", state_function.qs);
     }
 
@@ -303,9 +311,9 @@ mod tests {
             ],
         };
         let mut state_function = StateFunction::new();
-        state_function.iterate_game(state, vec![], vec![], 0., &mut 0);
+        state_function.iterate_game(&Problem::central_game(), &Solitaire::from_state(state), vec![], vec![], 0., &mut 0);
         
-        let result = HashMap::from([
+        let _result = HashMap::from([
             (String::from("32_1523.795903_69.843619"), (1, 2.0)),
             (String::from("32_1565.69579_72.843619"), (1, 2.0)),
             (String::from("31_1430.068762_67.681342"), (1, 1.0)),
@@ -319,13 +327,13 @@ mod tests {
     #[test]
     fn test_insert_value() {
         let mut hash = StateFunction::new();
-        hash.update_state_value_with_fn(String::from("hello"), String::from("state_hello"), f64::max, 1.);
-        hash.update_state_value_with_fn(String::from("dummy"), String::from("state_dummy"), f64::max, 100.);
-        hash.update_state_value_with_fn(String::from("hello"), String::from("state_hello"), f64::max, -100.);
-        hash.update_state_value_with_fn(String::from("hello"), String::from("state_hello"), f64::max, 1000.);
+        hash.update_state_value_with_fn(CanonicalKey(1), String::from("state_hello"), f64::max, 1.);
+        hash.update_state_value_with_fn(CanonicalKey(2), String::from("state_dummy"), f64::max, 100.);
+        hash.update_state_value_with_fn(CanonicalKey(1), String::from("state_hello"), f64::max, -100.);
+        hash.update_state_value_with_fn(CanonicalKey(1), String::from("state_hello"), f64::max, 1000.);
 
-        let expected = HashMap::from([(String::from("hello"), (3, 1000.,String::from("state_hello"))),
-                                      (String::from("dummy"), (1,  100.,String::from("state_dummy")))]);
+        let expected = HashMap::from([(CanonicalKey(1), (3, 1000.,String::from("state_hello"))),
+                                      (CanonicalKey(2), (1,  100.,String::from("state_dummy")))]);
 
         assert_eq!(expected, hash.qs);
     }
@@ -333,22 +341,78 @@ mod tests {
     #[test]
     fn test_get_value() {
         let mut hash = StateFunction::new();
-        hash.update_state_value_with_fn(String::from("hello"), String::from("state_hello"), f64::max, 1.);
-        hash.update_state_value_with_fn(String::from("dummy"), String::from("state_dummy"), f64::max, 100.);
+        hash.update_state_value_with_fn(CanonicalKey(1), String::from("state_hello"), f64::max, 1.);
+        hash.update_state_value_with_fn(CanonicalKey(2), String::from("state_dummy"), f64::max, 100.);
 
-        assert_eq!(Some(1.), hash.get_state_value(&String::from("hello")));
-        assert_eq!(None, hash.get_state_value(&String::from("hello_other")));
+        assert_eq!(Some(1.), hash.get_state_value(&CanonicalKey(1)));
+        assert_eq!(None, hash.get_state_value(&CanonicalKey(4)));
     }
 
     #[test]
     fn test_get_least_seen_value() {
         let mut hash = StateFunction::new();
-        hash.update_state_value_with_fn(String::from("hello"), String::from("state_hello"), f64::max, 1.);
-        hash.update_state_value_with_fn(String::from("dummy"), String::from("state_dummy"), f64::max, 100.);
-        hash.update_state_value_with_fn(String::from("hello"), String::from("state_hello"), f64::max, -100.);
-        hash.update_state_value_with_fn(String::from("hello"), String::from("state_hello"), f64::max, 1000.);
+        hash.update_state_value_with_fn(CanonicalKey(1), String::from("state_hello"), f64::max, 1.);
+        hash.update_state_value_with_fn(CanonicalKey(2), String::from("state_dummy"), f64::max, 100.);
+        hash.update_state_value_with_fn(CanonicalKey(1), String::from("state_hello"), f64::max, -100.);
+        hash.update_state_value_with_fn(CanonicalKey(1), String::from("state_hello"), f64::max, 1000.);
 
-        assert_eq!(String::from("dummy"), hash.get_least_seen_state(vec![&String::from("hello"), &String::from("dummy")]));
-        assert_eq!(String::from("never_seen"), hash.get_least_seen_state(vec![&String::from("hello"), &String::from("dummy"), &String::from("never_seen")]));
+        assert_eq!(CanonicalKey(2), hash.get_least_seen_state(vec![&CanonicalKey(1), &CanonicalKey(2)]));
+        assert_eq!(CanonicalKey(3), hash.get_least_seen_state(vec![&CanonicalKey(1), &CanonicalKey(2), &CanonicalKey(3)]));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_iterate_game_triangle() {
+        // the 15 hole triangle can be solved from a corner, so 13 pegs can be removed
+        let env = Solitaire::with_geometry(&Triangle::CRACKER_BARREL);
+        let problem = Problem::new(env.clone(), Target::AnySinglePeg);
+        let mut state_function = StateFunction::new();
+        state_function.iterate_game(&problem, &env, vec![], vec![], 0., &mut 0);
+        assert_eq!(state_function.get_state_value(&env.canonical_key()), Some(13. + SOLVED_BONUS));
+        assert_eq!(state_function.qs[&env.canonical_key()].2, "011111111111111");
+    }
+
+    #[test]
+    fn test_iterate_game_diagonal_rules() {
+        // two pegs next to each other on a diagonal can only be reduced with diagonal jumps
+        let board = Bitboard(Bitboard::bit(Point { x: 2, y: 2 }) | Bitboard::bit(Point { x: 3, y: 3 }));
+        for (rules, expected) in [(RuleSet::Standard, 0.), (RuleSet::Diagonal, 1. + SOLVED_BONUS)] {
+            let env = Solitaire::with_rules(&English, rules).with_board(board);
+            let problem = Problem::new(env.clone(), Target::AnySinglePeg);
+            let mut state_function = StateFunction::new();
+            state_function.iterate_game(&problem, &env, vec![], vec![], 0., &mut 0);
+            assert_eq!(state_function.get_state_value(&env.canonical_key()), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_iterate_game_with_pagodas() {
+        let problem = Problem::central_game();
+        let env = problem.start.with_board(english_endgame().start.board);
+        let mut plain = StateFunction::new();
+        plain.iterate_game(&problem, &env, vec![], vec![], 0., &mut 0);
+        let mut pruned = StateFunction { pagodas: Some(PagodaPruning::for_problem(&problem)), ..StateFunction::new() };
+        pruned.iterate_game(&problem, &env, vec![], vec![], 0., &mut 0);
+
+        // the solution is still found, but fewer positions are visited
+        assert_eq!(pruned.get_state_value(&problem.canonical_key(env.board)), Some(15. + SOLVED_BONUS));
+        assert_eq!(plain.get_state_value(&problem.canonical_key(env.board)), Some(15. + SOLVED_BONUS));
+        assert!(pruned.pagodas.unwrap().total_pruned() > 0);
+        assert!(pruned.qs.len() < plain.qs.len());
+        // positions ruled out get no value, positions on a solution keep theirs and no other position gets a
+        // higher one
+        let mut pagodas = PagodaPruning::for_problem(&problem);
+        for (key, (_, value, position)) in pruned.qs.iter() {
+            assert!(!pagodas.prunes(problem.layout().board_from_position_string(position).unwrap()));
+            let unpruned = plain.get_state_value(key).unwrap();
+            match unpruned == 15. + SOLVED_BONUS {
+                true => assert_eq!(*value, unpruned),
+                false => assert!(*value <= unpruned),
+            }
+        }
+
+        let mut endgame = StateFunction { tablebase: Some(Tablebase::build(&problem, 8)), ..StateFunction::new() };
+        endgame.iterate_game(&problem, &env, vec![], vec![], 0., &mut 0);
+        assert_eq!(endgame.get_state_value(&problem.canonical_key(env.board)), Some(15. + SOLVED_BONUS));
+        assert!(endgame.qs.len() < plain.qs.len());
+    }
+}
@@ -0,0 +1,334 @@
+use crate::board_geometry::BoardLayout;
+use crate::peg_solitaire_environment::{Bitboard, SolitaireAction};
+use crate::problem::Problem;
+use crate::q_learning::{CurvePoint, EpsilonSchedule, LearningCurve};
+use crate::reward::{RewardModel, DEFAULT_REWARDS};
+use crate::solver::PositionHasher;
+use crate::state_function::{ValueFunction, SOLVED_BONUS};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::sync::Arc;
+
+/// How the eligibility of a position grows when it is visited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Traces {
+    /// one is added to the trace
+    Accumulating,
+    /// the trace is set to one
+    Replacing,
+}
+
+/// What the value of a position is moved towards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backup {
+    /// the reward of the next jump plus the discounted value of the next position, after every jump
+    TemporalDifference,
+    /// the discounted rewards until the end of the game, once the game is over
+    MonteCarlo,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TdConfig {
+    /// step size
+    pub alpha: f64,
+    pub gamma: f64,
+    /// decay of the traces, 0 is TD(0) which only updates the position just left
+    pub lambda: f64,
+    pub traces: Traces,
+    pub backup: Backup,
+    pub epsilon: EpsilonSchedule,
+    pub episodes: u64,
+    /// episodes which are summarized in one point of the learning curve
+    pub window: u64,
+    pub seed: u64,
+    /// the value of positions which were never updated, see `QConfig::initial_value`
+    pub initial_value: f64,
+}
+
+impl Default for TdConfig {
+    fn default() -> Self {
+        TdConfig {
+            alpha: 0.1,
+            gamma: 1.,
+            lambda: 0.,
+            traces: Traces::Replacing,
+            backup: Backup::TemporalDifference,
+            epsilon: EpsilonSchedule::Linear { start: 1., end: 0.05, episodes: 10_000 },
+            episodes: 20_000,
+            window: 1000,
+            seed: 0,
+            initial_value: SOLVED_BONUS,
+        }
+    }
+}
+
+/// The rewards still to come from a position, stored once per compressed canonical position.
+pub struct StateValues {
+    pub values: HashMap<u64, f64, BuildHasherDefault<PositionHasher>>,
+    layout: Arc<BoardLayout>,
+    symmetries: Vec<usize>,
+    /// the value of positions without an entry
+    pub initial_value: f64,
+}
+
+impl StateValues {
+    pub fn new(problem: &Problem) -> Self {
+        StateValues { values: HashMap::default(), layout: problem.layout().clone(), symmetries: problem.symmetries(), initial_value: 0. }
+    }
+
+    pub fn key(&self, board: Bitboard) -> u64 {
+        self.layout.compress(self.layout.canonical_key_among(board, &self.symmetries).board())
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl ValueFunction for StateValues {
+    fn name(&self) -> String {
+        String::from("table")
+    }
+
+    fn value(&self, board: Bitboard) -> f64 {
+        self.values.get(&self.key(board)).copied().unwrap_or(self.initial_value)
+    }
+
+    fn gradient(&self, board: Bitboard) -> Vec<(u64, f64)> {
+        vec![(self.key(board), 1.)]
+    }
+
+    fn add_to_parameter(&mut self, parameter: u64, delta: f64) {
+        *self.values.entry(parameter).or_insert(self.initial_value) += delta;
+    }
+}
+
+fn state_value<V: ValueFunction + ?Sized>(problem: &Problem, values: &V, board: Bitboard) -> f64 {
+    match problem.layout().is_finished(board) {
+        true => 0.,
+        false => values.value(board),
+    }
+}
+
+// the jump with the highest reward plus discounted value of the position after it, ties at random
+fn greedy_action<V: ValueFunction + ?Sized>(
+    problem: &Problem,
+    rewards: &dyn RewardModel,
+    values: &V,
+    gamma: f64,
+    board: Bitboard,
+    rng: &mut StdRng,
+) -> Option<SolitaireAction> {
+    let layout = problem.layout();
+    let actions = layout.actions(board);
+    let scores: Vec<f64> = actions
+        .iter()
+        .map(|a| {
+            let next = layout.apply(board, *a);
+            rewards.reward(problem, board, next) + gamma * state_value(problem, values, next)
+        })
+        .collect();
+    let best = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let ties: Vec<SolitaireAction> = actions.iter().zip(scores).filter(|(_, s)| *s == best).map(|(a, _)| *a).collect();
+    ties.choose(rng).copied()
+}
+
+/// How well the greedy policy over learned values plays.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Evaluation {
+    pub games: u64,
+    pub solved: u64,
+    pub mean_pegs: f64,
+}
+
+impl Evaluation {
+    pub fn success_rate(&self) -> f64 {
+        self.solved as f64 / self.games as f64
+    }
+}
+
+/// Play `games` games which always take the jump to the position of the highest value, breaking ties at random.
+pub fn evaluate_greedy<V: ValueFunction + ?Sized>(
+    problem: &Problem,
+    rewards: &dyn RewardModel,
+    values: &V,
+    gamma: f64,
+    games: u64,
+    seed: u64,
+) -> Evaluation {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut evaluation = Evaluation { games, ..Default::default() };
+    let mut pegs = 0;
+    for _ in 0..games {
+        let mut env = problem.new_game();
+        while let Some(action) = greedy_action(problem, rewards, values, gamma, env.board, &mut rng) {
+            env.take_action(&action.value());
+        }
+        evaluation.solved += problem.is_solved(env.board) as u64;
+        pegs += env.board.count_pegs() as u64;
+    }
+    evaluation.mean_pegs = pegs as f64 / games as f64;
+    evaluation
+}
+
+/// Semi-gradient TD(λ) or Monte Carlo learning of the values of positions from epsilon greedy games. The traces
+/// follow the parameters of the value function. Since every jump
+/// removes a peg no position comes back within a game, so accumulating and replacing traces learn the same
+/// values for a table, they only differ for parameters which are shared between positions.
+pub struct TdLearner<'a, V: ValueFunction = StateValues> {
+    pub problem: &'a Problem,
+    pub values: V,
+    pub config: TdConfig,
+    /// how the jumps are rewarded, `DEFAULT_REWARDS` unless set
+    pub rewards: &'a dyn RewardModel,
+    rng: StdRng,
+}
+
+impl<'a> TdLearner<'a, StateValues> {
+    /// a learner of a table which starts at `config.initial_value`
+    pub fn new(problem: &'a Problem, config: TdConfig) -> Self {
+        let values = StateValues { initial_value: config.initial_value, ..StateValues::new(problem) };
+        TdLearner::with_values(problem, config, values)
+    }
+}
+
+impl<'a, V: ValueFunction> TdLearner<'a, V> {
+    pub fn with_values(problem: &'a Problem, config: TdConfig, values: V) -> Self {
+        TdLearner { problem, values, config, rewards: &DEFAULT_REWARDS, rng: StdRng::seed_from_u64(config.seed) }
+    }
+
+    /// Play one game with the epsilon of the episode and update the values, after every jump or at the end
+    /// of the game. Returns the pegs left and whether the target was reached.
+    pub fn run_episode(&mut self, episode: u64) -> (u32, bool) {
+        let epsilon = self.config.epsilon.epsilon(episode);
+        let (gamma, lambda, alpha) = (self.config.gamma, self.config.lambda, self.config.alpha);
+        let mut env = self.problem.new_game();
+        // the eligibility of every parameter touched in the game so far
+        let mut traces: HashMap<u64, f64, BuildHasherDefault<PositionHasher>> = HashMap::default();
+        // the positions of the game with the reward of the jump from them
+        let mut game: Vec<(Bitboard, f64)> = Vec::new();
+        while let Some(actions) = env.actions() {
+            let action = match self.rng.gen::<f64>() < epsilon {
+                true => *actions.choose(&mut self.rng).unwrap(),
+                false => greedy_action(self.problem, self.rewards, &self.values, gamma, env.board, &mut self.rng).unwrap(),
+            };
+            let board = env.board;
+            env.take_action(&action.value());
+            let reward = self.rewards.reward(self.problem, board, env.board);
+            if self.config.backup == Backup::MonteCarlo {
+                game.push((board, reward));
+                continue;
+            }
+            let delta = reward + gamma * state_value(self.problem, &self.values, env.board) - self.values.value(board);
+            for (parameter, derivative) in self.values.gradient(board) {
+                let trace = traces.entry(parameter).or_insert(0.);
+                match self.config.traces {
+                    Traces::Accumulating => *trace += derivative,
+                    Traces::Replacing => *trace = derivative,
+                }
+            }
+            for (parameter, trace) in traces.iter_mut() {
+                self.values.add_to_parameter(*parameter, alpha * delta * *trace);
+                *trace *= gamma * lambda;
+            }
+            traces.retain(|_, trace| trace.abs() > 1e-6);
+        }
+        let mut ret = 0.;
+        for (board, reward) in game.into_iter().rev() {
+            ret = reward + gamma * ret;
+            let error = ret - self.values.value(board);
+            for (parameter, derivative) in self.values.gradient(board) {
+                self.values.add_to_parameter(parameter, alpha * error * derivative);
+            }
+        }
+        (env.board.count_pegs(), self.problem.is_solved(env.board))
+    }
+
+    /// Run `config.episodes` episodes and summarize every `config.window` of them.
+    pub fn train(&mut self) -> LearningCurve {
+        let mut curve = LearningCurve::default();
+        let (mut solved, mut pegs, mut played) = (0, 0, 0);
+        for episode in 0..self.config.episodes {
+            let (left, success) = self.run_episode(episode);
+            solved += success as u64;
+            pegs += left as u64;
+            played += 1;
+            if played == self.config.window || episode + 1 == self.config.episodes {
+                curve.points.push(CurvePoint {
+                    episodes: episode + 1,
+                    success_rate: solved as f64 / played as f64,
+                    mean_pegs: pegs as f64 / played as f64,
+                    epsilon: self.config.epsilon.epsilon(episode),
+                });
+                (solved, pegs, played) = (0, 0, 0);
+            }
+        }
+        curve
+    }
+
+    pub fn evaluate(&self, games: u64, seed: u64) -> Evaluation {
+        evaluate_greedy(self.problem, self.rewards, &self.values, self.config.gamma, games, seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_geometry::Triangle;
+    use crate::peg_solitaire_environment::{Point, Solitaire};
+    use crate::problem::Target;
+    use crate::reward::TerminalSuccess;
+    use crate::test_problems::triangle;
+
+    #[test]
+    fn test_td_learns_the_triangle() {
+        let problem = triangle();
+        for (lambda, backup) in [(0., Backup::TemporalDifference), (0.8, Backup::TemporalDifference), (0., Backup::MonteCarlo)] {
+            let config = TdConfig { alpha: 0.2, lambda, backup, episodes: 5000, window: 500, ..Default::default() };
+            let config = TdConfig { epsilon: EpsilonSchedule::Linear { start: 1., end: 0.05, episodes: 4000 }, ..config };
+            let mut learner = TdLearner::new(&problem, config);
+            let before = learner.evaluate(100, 1);
+            let curve = learner.train();
+            let after = learner.evaluate(100, 1);
+            assert!(curve.points.last().unwrap().mean_pegs < curve.points[0].mean_pegs, "{}\n{}", lambda, curve.report());
+            assert!(after.success_rate() > 0.9 && after.success_rate() > before.success_rate(), "{} {:?} {:?}", lambda, backup, after);
+            assert!(after.mean_pegs < before.mean_pegs);
+        }
+    }
+
+    #[test]
+    fn test_td_zero_update() {
+        // a single jump is left, its value is learned in one step with a step size of one
+        let layout = Arc::new(BoardLayout::new(&Triangle::CRACKER_BARREL));
+        let board = Bitboard(Bitboard::bit(Point { x: 0, y: 2 }) | Bitboard::bit(Point { x: 0, y: 1 }));
+        let problem = Problem::new(Solitaire::from_layout(layout, board), Target::AnySinglePeg);
+        let config = TdConfig { alpha: 1., episodes: 1, initial_value: 0., ..Default::default() };
+        let mut learner = TdLearner::new(&problem, config);
+        assert_eq!(learner.run_episode(0), (1, true));
+        assert_eq!(learner.values.value(board), 1. + SOLVED_BONUS);
+        assert_eq!(learner.values.len(), 1);
+
+        let mut terminal = TdLearner::new(&problem, config);
+        terminal.rewards = &TerminalSuccess;
+        terminal.run_episode(0);
+        assert_eq!(terminal.values.value(board), 1.);
+    }
+
+    #[test]
+    fn test_traces_agree_without_returning_positions() {
+        let problem = triangle();
+        let config = TdConfig { lambda: 0.9, episodes: 300, ..Default::default() };
+        let mut accumulating = TdLearner::new(&problem, TdConfig { traces: Traces::Accumulating, ..config });
+        let mut replacing = TdLearner::new(&problem, TdConfig { traces: Traces::Replacing, ..config });
+        accumulating.train();
+        replacing.train();
+        assert_eq!(accumulating.values.values, replacing.values.values);
+    }
+}
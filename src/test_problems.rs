@@ -0,0 +1,26 @@
+use crate::board_geometry::{BoardLayout, Triangle};
+use crate::peg_solitaire_environment::{Point, Solitaire, SolitaireAction};
+use crate::problem::{Problem, Target};
+use std::sync::Arc;
+
+/// the position after 16 moves of a solution of the central game, which is solved with 15 more jumps
+pub fn english_endgame() -> Problem {
+    let layout = BoardLayout::english();
+    let board = layout.board_from_position_string("000001001000100000100111111111111").unwrap();
+    Problem::new(Solitaire::from_layout(layout, board), Target::SinglePegAt(Point { x: 3, y: 3 }))
+}
+
+/// the triangle with 15 holes, starting and finishing in its top corner
+pub fn triangle() -> Problem {
+    Problem::single_vacancy(Arc::new(BoardLayout::new(&Triangle::CRACKER_BARREL)), Point { x: 0, y: 0 }, Point { x: 0, y: 0 })
+}
+
+/// every jump of the solution is legal and it ends on the target
+pub fn assert_solves(problem: &Problem, solution: &[SolitaireAction]) {
+    let mut env = problem.new_game();
+    for action in solution {
+        assert!(env.actions().unwrap_or_default().contains(action));
+        env.take_action(&action.value());
+    }
+    assert!(problem.is_solved(env.board));
+}